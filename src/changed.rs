@@ -0,0 +1,154 @@
+//! Selection of the crates affected by changed files, like the changes in a
+//! Git repository or the files passed by pre-commit, optionally including
+//! the crates that depend on the changed ones through `path` dependencies.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::discovery::{normalize_path, read_manifest};
+
+fn canonical_path(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| {
+        normalize_path(&std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()))
+    })
+}
+
+/// Run a `git` command in a directory, returning its stdout or its stderr
+/// if it fails.
+fn git(path: &Path, args: &[&str]) -> Result<String, String> {
+    let output = std::process::Command::new("git")
+        .args(args)
+        .current_dir(path)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .map_err(|error| format!("Binary git could not be executed: {error}"))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Files changed since a Git reference, including uncommitted changes and
+/// untracked files that are not ignored.
+///
+/// Renamed files are reported with both their old and new paths.
+pub(crate) fn changed_files(path: &Path, reference: &str) -> Result<Vec<PathBuf>, String> {
+    let toplevel = git(path, &["rev-parse", "--show-toplevel"])?;
+    let toplevel = PathBuf::from(toplevel.trim());
+    let diff = git(
+        path,
+        &["diff", "--name-only", "--no-renames", "-z", reference, "--"],
+    )?;
+    let untracked = git(
+        &toplevel,
+        &["ls-files", "--others", "--exclude-standard", "-z"],
+    )?;
+    Ok(diff
+        .split('\0')
+        .chain(untracked.split('\0'))
+        .filter(|file| !file.is_empty())
+        .map(|file| toplevel.join(file))
+        .collect())
+}
+
+/// Directory of the crate that owns a file, which is the closest parent
+/// directory with a `Cargo.toml`. Works for deleted files too.
+fn owning_crate(file: &Path) -> Option<PathBuf> {
+    file.ancestors()
+        .skip(1)
+        .find(|dir| dir.join("Cargo.toml").is_file())
+        .map(Path::to_path_buf)
+}
+
+/// Dependencies tables of a manifest, including the target-specific ones.
+fn dependencies_tables(manifest: &toml::Value) -> Vec<&toml::Table> {
+    const TABLES: &[&str] = &["dependencies", "dev-dependencies", "build-dependencies"];
+    let targets = manifest
+        .get("target")
+        .and_then(toml::Value::as_table)
+        .into_iter()
+        .flat_map(|targets| targets.values());
+    std::iter::once(manifest)
+        .chain(targets)
+        .flat_map(|table| {
+            TABLES
+                .iter()
+                .filter_map(|name| table.get(*name)?.as_table())
+        })
+        .collect()
+}
+
+/// Directories of the crates that a crate depends on through `path`
+/// dependencies, including the ones inherited from its workspace.
+fn path_dependencies(crate_path: &Path) -> Vec<PathBuf> {
+    let Some(manifest) = read_manifest(crate_path) else {
+        return Vec::new();
+    };
+    let mut workspace = None;
+    let mut paths = Vec::new();
+    for (name, dependency) in dependencies_tables(&manifest)
+        .into_iter()
+        .flat_map(|table| table.iter())
+    {
+        if let Some(path) = dependency.get("path").and_then(toml::Value::as_str) {
+            paths.push(canonical_path(&crate_path.join(path)));
+        } else if dependency.get("workspace").and_then(toml::Value::as_bool) == Some(true) {
+            let (root, root_manifest) = workspace.get_or_insert_with(|| {
+                crate_path
+                    .ancestors()
+                    .find_map(|dir| {
+                        read_manifest(dir)
+                            .filter(|manifest| manifest.get("workspace").is_some())
+                            .map(|manifest| (dir.to_path_buf(), manifest))
+                    })
+                    .unwrap_or_else(|| (PathBuf::new(), toml::Value::Table(toml::Table::new())))
+            });
+            let path = root_manifest
+                .get("workspace")
+                .and_then(|workspace| workspace.get("dependencies")?.get(name)?.get("path"))
+                .and_then(toml::Value::as_str);
+            if let Some(path) = path {
+                paths.push(canonical_path(&root.join(path)));
+            }
+        }
+    }
+    paths
+}
+
+/// Keep the crates that own some of the changed files and, if
+/// `include_dependents` is `true`, the crates that depend on them, directly
+/// or transitively, through `path` dependencies.
+pub(crate) fn affected_crates(
+    crates_paths: Vec<PathBuf>,
+    changed_files: &[PathBuf],
+    include_dependents: bool,
+) -> Vec<PathBuf> {
+    let changed_crates = changed_files
+        .iter()
+        .filter_map(|file| owning_crate(file))
+        .map(|crate_path| canonical_path(&crate_path))
+        .collect::<HashSet<_>>();
+
+    crates_paths
+        .into_iter()
+        .filter(|crate_path| {
+            let crate_path = canonical_path(crate_path);
+            if !include_dependents {
+                return changed_crates.contains(&crate_path);
+            }
+            let mut visited = HashSet::from([crate_path.clone()]);
+            let mut pending = vec![crate_path];
+            while let Some(crate_path) = pending.pop() {
+                if changed_crates.contains(&crate_path) {
+                    return true;
+                }
+                for dependency in path_dependencies(&crate_path) {
+                    if visited.insert(dependency.clone()) {
+                        pending.push(dependency);
+                    }
+                }
+            }
+            false
+        })
+        .collect()
+}
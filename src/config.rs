@@ -0,0 +1,495 @@
+//! Configuration of how to test the crates, read from the
+//! `[package.metadata.wasm-pack-test-all]` table of the `Cargo.toml` of each
+//! crate, the `[workspace.metadata.wasm-pack-test-all]` table of the
+//! workspace and the `wasm-pack-test-all.toml` file.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::discovery;
+use crate::runner::CrateInvocation;
+use crate::scan::TestEnvironment;
+
+/// Runtimes in which `wasm-pack test` can run the tests, selected with the
+/// `--<runtime>` flags.
+pub(crate) const RUNTIMES: &[&str] = &["node", "chrome", "firefox", "safari"];
+
+/// Get the runtime selected by a `wasm-pack test` option, like `node` for `--node`.
+pub(crate) fn runtime_of_option(option: &str) -> Option<&'static str> {
+    let runtime = option.strip_prefix("--")?;
+    RUNTIMES.iter().find(|known| **known == runtime).copied()
+}
+
+/// Select the runtime of a crate from the environment where its tests run:
+/// the browsers selected in `wasm_pack_test_options`, or Chrome if none, for
+/// the tests configured with `wasm_bindgen_test_configure!`, and Node.js for
+/// the rest. The runtimes selected that don't match are removed. Chrome runs
+/// headless when it is selected here, as there may be no display, unless
+/// `--headless` is already passed in the options or in the
+/// `crate_wasm_pack_args` of the crate configuration.
+pub(crate) fn auto_runtime_options(
+    wasm_pack_test_options: &[String],
+    crate_wasm_pack_args: &[String],
+    environment: Option<TestEnvironment>,
+) -> Vec<String> {
+    let runs_in_browser = environment.is_some();
+    let mut options = wasm_pack_test_options
+        .iter()
+        .filter(|option| {
+            runtime_of_option(option).is_none_or(|runtime| (runtime != "node") == runs_in_browser)
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    if !options
+        .iter()
+        .any(|option| runtime_of_option(option).is_some())
+    {
+        if !runs_in_browser {
+            options.insert(0, "--node".to_string());
+        } else if options
+            .iter()
+            .chain(crate_wasm_pack_args)
+            .any(|option| option == "--headless")
+        {
+            options.insert(0, "--chrome".to_string());
+        } else {
+            options.splice(0..0, ["--chrome".to_string(), "--headless".to_string()]);
+        }
+    }
+    options
+}
+
+/// Parse durations like `90`, `90s`, `1m30s`, `500ms` or `1h`.
+///
+/// Numbers without unit are seconds. Zero durations are invalid.
+pub(crate) fn parse_duration(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.parse::<u64>() {
+        return (seconds > 0).then(|| Duration::from_secs(seconds));
+    }
+    let mut duration = Duration::ZERO;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let digits_end = rest.find(|c: char| !c.is_ascii_digit())?;
+        let number = rest[..digits_end].parse::<u64>().ok()?;
+        rest = &rest[digits_end..];
+        let unit_end = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        duration += match &rest[..unit_end] {
+            "ms" => Duration::from_millis(number),
+            "s" => Duration::from_secs(number),
+            "m" => Duration::from_secs(number * 60),
+            "h" => Duration::from_secs(number * 60 * 60),
+            _ => return None,
+        };
+        rest = &rest[unit_end..];
+    }
+    (!duration.is_zero()).then_some(duration)
+}
+
+/// Format a duration in the format accepted by [`parse_duration`].
+pub(crate) fn format_duration(duration: Duration) -> String {
+    if duration.subsec_millis() != 0 {
+        return format!("{}ms", duration.as_millis());
+    }
+    let seconds = duration.as_secs();
+    [
+        (seconds / 3600, "h"),
+        (seconds / 60 % 60, "m"),
+        (seconds % 60, "s"),
+    ]
+    .iter()
+    .filter(|(count, _)| *count > 0)
+    .map(|(count, unit)| format!("{count}{unit}"))
+    .collect()
+}
+
+#[derive(Default)]
+pub(crate) struct CrateConfig {
+    /// Don't test the crate.
+    pub(crate) skip: bool,
+    /// Options passed to `wasm-pack test`.
+    pub(crate) wasm_pack_args: Vec<String>,
+    /// Options passed to `cargo` after `--`.
+    pub(crate) cargo_args: Vec<String>,
+    /// Features of the crate to enable.
+    pub(crate) features: Vec<String>,
+    /// Environment variables set for `wasm-pack test`.
+    pub(crate) env: Vec<(String, String)>,
+    /// Runtimes in which the crate can be tested, or all if not defined.
+    pub(crate) runtimes: Option<Vec<String>>,
+}
+
+fn string_array(value: &toml::Value, key: &str) -> Result<Vec<String>, String> {
+    value
+        .as_array()
+        .and_then(|values| {
+            values
+                .iter()
+                .map(|value| value.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+        })
+        .ok_or_else(|| format!("`{key}` must be an array of strings"))
+}
+
+fn integer(value: &toml::Value, key: &str, minimum: usize) -> Result<usize, String> {
+    value
+        .as_integer()
+        .and_then(|integer| usize::try_from(integer).ok())
+        .filter(|integer| *integer >= minimum)
+        .ok_or_else(|| match minimum {
+            0 => format!("`{key}` must be a non-negative integer"),
+            _ => format!("`{key}` must be a positive integer"),
+        })
+}
+
+fn duration(value: &toml::Value, key: &str) -> Result<Duration, String> {
+    value
+        .as_str()
+        .and_then(parse_duration)
+        .ok_or_else(|| format!("`{key}` must be a duration like \"90s\", \"5m\" or \"1h30m\""))
+}
+
+fn check_runtimes(runtimes: &[String]) -> Result<(), String> {
+    match runtimes
+        .iter()
+        .find(|runtime| !RUNTIMES.contains(&runtime.as_str()))
+    {
+        Some(runtime) => Err(format!(
+            "unknown runtime `{runtime}` in `runtimes`, expected one of {}",
+            RUNTIMES.join(", ")
+        )),
+        None => Ok(()),
+    }
+}
+
+impl CrateConfig {
+    /// Read the configuration of a crate, which is empty if its
+    /// `Cargo.toml` has no `[package.metadata.wasm-pack-test-all]` table.
+    pub(crate) fn from_crate(crate_path: &Path) -> Result<Self, String> {
+        let table = discovery::read_manifest(crate_path).and_then(|manifest| {
+            manifest
+                .get("package")?
+                .get("metadata")?
+                .get("wasm-pack-test-all")
+                .cloned()
+        });
+        match table {
+            Some(table) => Self::from_table(&table),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Parse a configuration table, failing on unknown keys and values of
+    /// the wrong type.
+    pub(crate) fn from_table(table: &toml::Value) -> Result<Self, String> {
+        let table = table
+            .as_table()
+            .ok_or_else(|| "the configuration must be a table".to_string())?;
+        let mut config = Self::default();
+        for (key, value) in table {
+            match key.as_str() {
+                "skip" => {
+                    config.skip = value
+                        .as_bool()
+                        .ok_or_else(|| "`skip` must be a boolean".to_string())?;
+                }
+                "wasm-pack-args" => config.wasm_pack_args = string_array(value, key)?,
+                "cargo-args" => config.cargo_args = string_array(value, key)?,
+                "features" => config.features = string_array(value, key)?,
+                "env" => {
+                    config.env = value
+                        .as_table()
+                        .and_then(|env| {
+                            env.iter()
+                                .map(|(name, value)| {
+                                    value
+                                        .as_str()
+                                        .map(|value| (name.clone(), value.to_string()))
+                                })
+                                .collect::<Option<Vec<_>>>()
+                        })
+                        .ok_or_else(|| "`env` must be a table of strings".to_string())?;
+                }
+                "runtimes" => {
+                    let runtimes = string_array(value, key)?;
+                    check_runtimes(&runtimes)?;
+                    config.runtimes = Some(runtimes);
+                }
+                key => return Err(format!("unknown key `{key}`")),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Select the runtimes of the crate among the `defaults` of the
+    /// configuration files, used when no runtime is selected in the command
+    /// line. The runtimes selected in its `wasm-pack-args` replace them, and
+    /// its allowed runtimes too if none of the defaults is allowed.
+    pub(crate) fn default_runtimes(&self, defaults: &[String]) -> Vec<String> {
+        if self
+            .wasm_pack_args
+            .iter()
+            .any(|option| runtime_of_option(option).is_some())
+        {
+            return Vec::new();
+        }
+        let Some(allowed_runtimes) = &self.runtimes else {
+            return defaults.to_vec();
+        };
+        let allowed_defaults = defaults
+            .iter()
+            .filter(|runtime| allowed_runtimes.contains(runtime))
+            .cloned()
+            .collect::<Vec<_>>();
+        if allowed_defaults.is_empty() && !defaults.is_empty() {
+            allowed_runtimes.clone()
+        } else {
+            allowed_defaults
+        }
+    }
+
+    /// Build the invocation of `wasm-pack test` for a crate merging its
+    /// configuration with the options passed in the command line.
+    ///
+    /// Returns the reason why the crate must not be tested if it's skipped
+    /// or if none of the runtimes selected is allowed for the crate.
+    pub(crate) fn invocation(
+        &self,
+        crate_path: &Path,
+        wasm_pack_test_options: &[String],
+        cargo_test_options: &[String],
+    ) -> Result<CrateInvocation, String> {
+        if self.skip {
+            return Err("disabled in package metadata".to_string());
+        }
+
+        let mut wasm_pack_test_options = wasm_pack_test_options
+            .iter()
+            .chain(&self.wasm_pack_args)
+            .cloned()
+            .collect::<Vec<_>>();
+        if let Some(allowed_runtimes) = &self.runtimes {
+            let is_allowed =
+                |runtime: &str| allowed_runtimes.iter().any(|allowed| allowed == runtime);
+            let selected_runtimes = wasm_pack_test_options
+                .iter()
+                .filter_map(|option| runtime_of_option(option))
+                .collect::<Vec<_>>();
+            if !selected_runtimes.is_empty()
+                && !selected_runtimes.iter().any(|runtime| is_allowed(runtime))
+            {
+                return Err(format!(
+                    "runtime {} not allowed",
+                    selected_runtimes.join(", ")
+                ));
+            }
+            wasm_pack_test_options
+                .retain(|option| runtime_of_option(option).is_none_or(is_allowed));
+        }
+
+        // The options of the crate go right after `--`, so the options
+        // passed to the test binary after another `--` stay at the end
+        let mut crate_cargo_args = self.cargo_args.clone();
+        if !self.features.is_empty() {
+            crate_cargo_args.push("--features".to_string());
+            crate_cargo_args.push(self.features.join(","));
+        }
+        let cargo_test_options = if crate_cargo_args.is_empty() {
+            cargo_test_options.to_vec()
+        } else {
+            std::iter::once("--".to_string())
+                .chain(crate_cargo_args)
+                .chain(cargo_test_options.iter().skip(1).cloned())
+                .collect()
+        };
+
+        Ok(CrateInvocation {
+            crate_path: crate_path.to_path_buf(),
+            wasm_pack_test_options,
+            cargo_test_options,
+            envs: self.env.clone(),
+            runtime: None,
+        })
+    }
+}
+
+/// Defaults for all the crates of a workspace, which are overridden by the
+/// options passed in the command line and the configuration of each crate.
+#[cfg(feature = "workspace")]
+#[derive(Default)]
+pub(crate) struct WorkspaceConfig {
+    /// Runtimes in which each crate is tested, one run per runtime, when none
+    /// is selected in the command line.
+    pub(crate) runtimes: Vec<String>,
+    pub(crate) jobs: Option<usize>,
+    pub(crate) timeout: Option<Duration>,
+    /// Patterns of paths to skip, relative to the workspace root.
+    pub(crate) exclude: Vec<String>,
+    /// Options passed to `wasm-pack test` before the ones of the command line.
+    pub(crate) wasm_pack_args: Vec<String>,
+    /// Options passed to `cargo` before the ones of the command line.
+    pub(crate) cargo_args: Vec<String>,
+}
+
+#[cfg(feature = "workspace")]
+impl WorkspaceConfig {
+    /// Read the defaults of a workspace, which are empty if its `Cargo.toml`
+    /// has no `[workspace.metadata.wasm-pack-test-all]` table.
+    pub(crate) fn from_workspace(workspace_root: &Path) -> Result<Self, String> {
+        let table = discovery::read_manifest(workspace_root).and_then(|manifest| {
+            manifest
+                .get("workspace")?
+                .get("metadata")?
+                .get("wasm-pack-test-all")
+                .cloned()
+        });
+        match table {
+            Some(table) => Self::from_table(&table),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Parse a defaults table, failing on unknown keys and values of the
+    /// wrong type.
+    pub(crate) fn from_table(table: &toml::Value) -> Result<Self, String> {
+        let table = table
+            .as_table()
+            .ok_or_else(|| "the configuration must be a table".to_string())?;
+        let mut config = Self::default();
+        for (key, value) in table {
+            match key.as_str() {
+                "runtimes" => {
+                    config.runtimes = string_array(value, key)?;
+                    check_runtimes(&config.runtimes)?;
+                }
+                "jobs" => config.jobs = Some(integer(value, key, 1)?),
+                "timeout" => config.timeout = Some(duration(value, key)?),
+                "exclude" => config.exclude = string_array(value, key)?,
+                "wasm-pack-args" => config.wasm_pack_args = string_array(value, key)?,
+                "cargo-args" => config.cargo_args = string_array(value, key)?,
+                key => return Err(format!("unknown key `{key}`")),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Name of the configuration file, which is searched in the tested path and
+/// its parent directories.
+pub(crate) const CONFIG_FILE: &str = "wasm-pack-test-all.toml";
+
+/// Find the closest configuration file walking up from `path`.
+pub(crate) fn find_config_file(path: &Path) -> Option<PathBuf> {
+    let path = std::path::absolute(path).ok()?;
+    path.ancestors()
+        .map(|dir| dir.join(CONFIG_FILE))
+        .find(|config_file| config_file.is_file())
+}
+
+/// Settings of a configuration file or one of its profiles. Settings not
+/// defined are `None`.
+#[derive(Default)]
+pub(crate) struct Settings {
+    pub(crate) runtimes: Option<Vec<String>>,
+    pub(crate) jobs: Option<usize>,
+    pub(crate) retries: Option<usize>,
+    pub(crate) timeout: Option<Duration>,
+    /// Path of the JUnit report, relative to the configuration file.
+    pub(crate) junit: Option<PathBuf>,
+    /// Patterns of paths to skip, relative to the configuration file.
+    pub(crate) exclude: Option<Vec<String>>,
+    pub(crate) packages: Option<Vec<String>>,
+    pub(crate) exclude_packages: Option<Vec<String>>,
+    pub(crate) wasm_pack_args: Option<Vec<String>>,
+    pub(crate) cargo_args: Option<Vec<String>>,
+}
+
+impl Settings {
+    /// Parse a table of settings, skipping the keys in `ignored_keys`.
+    fn from_table(table: &toml::Table, ignored_keys: &[&str]) -> Result<Self, String> {
+        let mut settings = Self::default();
+        for (key, value) in table {
+            match key.as_str() {
+                "runtimes" => {
+                    let runtimes = string_array(value, key)?;
+                    check_runtimes(&runtimes)?;
+                    settings.runtimes = Some(runtimes);
+                }
+                "jobs" => settings.jobs = Some(integer(value, key, 1)?),
+                "retries" => settings.retries = Some(integer(value, key, 0)?),
+                "timeout" => settings.timeout = Some(duration(value, key)?),
+                "junit" => {
+                    settings.junit = Some(
+                        value
+                            .as_str()
+                            .map(PathBuf::from)
+                            .ok_or_else(|| "`junit` must be a path".to_string())?,
+                    );
+                }
+                "exclude" => settings.exclude = Some(string_array(value, key)?),
+                "packages" => settings.packages = Some(string_array(value, key)?),
+                "exclude-packages" => settings.exclude_packages = Some(string_array(value, key)?),
+                "wasm-pack-args" => settings.wasm_pack_args = Some(string_array(value, key)?),
+                "cargo-args" => settings.cargo_args = Some(string_array(value, key)?),
+                key if ignored_keys.contains(&key) => {}
+                key => return Err(format!("unknown key `{key}`")),
+            }
+        }
+        Ok(settings)
+    }
+
+    /// Override the settings with the ones defined in `other`.
+    fn merge(self, other: Self) -> Self {
+        Self {
+            runtimes: other.runtimes.or(self.runtimes),
+            jobs: other.jobs.or(self.jobs),
+            retries: other.retries.or(self.retries),
+            timeout: other.timeout.or(self.timeout),
+            junit: other.junit.or(self.junit),
+            exclude: other.exclude.or(self.exclude),
+            packages: other.packages.or(self.packages),
+            exclude_packages: other.exclude_packages.or(self.exclude_packages),
+            wasm_pack_args: other.wasm_pack_args.or(self.wasm_pack_args),
+            cargo_args: other.cargo_args.or(self.cargo_args),
+        }
+    }
+
+    /// Read the settings of a configuration file. The settings of the
+    /// `[profile.<name>]` table of `profile` override the top-level ones.
+    pub(crate) fn from_file(config_file: &Path, profile: Option<&str>) -> Result<Self, String> {
+        let content = std::fs::read_to_string(config_file).map_err(|error| error.to_string())?;
+        let table = toml::from_str::<toml::Table>(&content).map_err(|error| error.to_string())?;
+        let mut selected_profile = None;
+        if let Some(profiles) = table.get("profile") {
+            let profiles = profiles
+                .as_table()
+                .ok_or_else(|| "`profile` must be a table of profiles".to_string())?;
+            // All the profiles are parsed to report their errors
+            for (name, profile_table) in profiles {
+                let profile_settings = profile_table
+                    .as_table()
+                    .ok_or_else(|| format!("the profile `{name}` must be a table"))
+                    .and_then(|profile_table| Self::from_table(profile_table, &[]))
+                    .map_err(|error| format!("{error} in the profile `{name}`"))?;
+                if Some(name.as_str()) == profile {
+                    selected_profile = Some(profile_settings);
+                }
+            }
+        }
+
+        let settings = Self::from_table(&table, &["profile"])?;
+        let settings = match (profile, selected_profile) {
+            (Some(profile), None) => {
+                return Err(format!("the profile `{profile}` is not defined"));
+            }
+            (_, Some(profile_settings)) => settings.merge(profile_settings),
+            (None, None) => settings,
+        };
+        let base = config_file.parent().unwrap_or(Path::new(""));
+        Ok(Self {
+            junit: settings.junit.map(|junit| base.join(junit)),
+            ..settings
+        })
+    }
+}
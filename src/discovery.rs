@@ -0,0 +1,508 @@
+use std::path::{Path, PathBuf};
+
+use crate::{glob, scan};
+
+/// Names of directories that never contain crates to test, like build
+/// output and version control directories.
+const SKIPPED_DIRECTORIES: &[&str] = &["target", "node_modules", ".git", ".hg", ".svn"];
+
+/// Whether a directory must not be walked when discovering crates.
+///
+/// `pkg` directories are skipped only inside crates, where they are the
+/// output of `wasm-pack build`.
+fn is_skipped_directory(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    SKIPPED_DIRECTORIES.contains(&name)
+        || (name == "pkg"
+            && path
+                .parent()
+                .is_some_and(|parent| parent.join("Cargo.toml").is_file()))
+}
+
+/// Files with gitignore-style patterns of paths to skip during discovery,
+/// read in every directory walked.
+const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore", ".wasm-pack-test-all-ignore"];
+
+struct IgnoreRule {
+    pattern: String,
+    /// Pattern starting with `!`, which includes again paths excluded by
+    /// previous patterns.
+    negated: bool,
+    /// Pattern ending with `/`, which only matches directories.
+    directories_only: bool,
+}
+
+/// Gitignore-style patterns relative to a directory.
+struct IgnoreRules {
+    base: PathBuf,
+    rules: Vec<IgnoreRule>,
+}
+
+impl IgnoreRules {
+    fn new(base: &Path) -> Self {
+        Self {
+            base: std::path::absolute(base).unwrap_or_else(|_| base.to_path_buf()),
+            rules: Vec::new(),
+        }
+    }
+
+    /// Add a pattern with the syntax of `.gitignore` files.
+    fn add(&mut self, line: &str) {
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() || line.starts_with('#') {
+            return;
+        }
+        // Trailing spaces are ignored unless escaped
+        let mut pattern = line.trim_end_matches(' ').to_string();
+        if pattern.ends_with('\\') && line.len() > pattern.len() {
+            pattern.push(' ');
+        }
+        let negated = pattern.starts_with('!');
+        // `!` negates the pattern, while `\!` and `\#` are escaped literals
+        if negated || pattern.starts_with("\\!") || pattern.starts_with("\\#") {
+            pattern.remove(0);
+        }
+        let directories_only = pattern.ends_with('/');
+        if directories_only {
+            pattern.pop();
+        }
+        if pattern.is_empty() {
+            return;
+        }
+        // Patterns without a slash match at any level below the base
+        if let Some(anchored) = pattern.strip_prefix('/') {
+            pattern = anchored.to_string();
+        } else if !pattern.contains('/') {
+            pattern = format!("**/{pattern}");
+        }
+        self.rules.push(IgnoreRule {
+            pattern,
+            negated,
+            directories_only,
+        });
+    }
+
+    /// Read the ignore files of a directory.
+    fn from_dir(dir: &Path) -> Option<Self> {
+        let mut rules = Self::new(dir);
+        for ignore_file in IGNORE_FILES {
+            if let Ok(content) = std::fs::read_to_string(dir.join(ignore_file)) {
+                for line in content.lines() {
+                    rules.add(line);
+                }
+            }
+        }
+        (!rules.rules.is_empty()).then_some(rules)
+    }
+
+    /// Whether the path is ignored (`Some(true)`) or included again by a
+    /// negated pattern (`Some(false)`) by the last pattern matching it.
+    fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let path = std::path::absolute(path).ok()?;
+        let relative = path
+            .strip_prefix(&self.base)
+            .ok()?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| {
+                (is_dir || !rule.directories_only) && glob::matches(&rule.pattern, &relative)
+            })
+            .map(|rule| !rule.negated)
+    }
+}
+
+/// Ignore rules that apply to the paths walked during discovery.
+struct Ignores {
+    /// Rules of ignore files, from the outermost directory to the innermost.
+    files: Vec<IgnoreRules>,
+    /// Rules passed with `--exclude`, which take precedence.
+    excludes: IgnoreRules,
+}
+
+impl Ignores {
+    /// Create the ignore rules to walk `root`, including the ignore files of
+    /// its parent directories inside the same Git repository.
+    fn new(root: &Path, excludes: &[String]) -> Self {
+        let mut cli_rules = IgnoreRules::new(root);
+        for exclude in excludes {
+            cli_rules.add(exclude);
+        }
+
+        let mut files = Vec::new();
+        if let Ok(root) = std::path::absolute(root) {
+            // The root itself can be the root of the repository
+            let mut parents = Vec::new();
+            for dir in root.ancestors() {
+                if dir != root {
+                    parents.push(dir);
+                }
+                if dir.join(".git").exists() {
+                    files.extend(
+                        parents
+                            .iter()
+                            .rev()
+                            .filter_map(|p| IgnoreRules::from_dir(p)),
+                    );
+                    break;
+                }
+            }
+        }
+
+        Self {
+            files,
+            excludes: cli_rules,
+        }
+    }
+
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        self.files
+            .iter()
+            .chain(std::iter::once(&self.excludes))
+            .fold(false, |ignored, rules| {
+                rules.matched(path, is_dir).unwrap_or(ignored)
+            })
+    }
+}
+
+/// Whether a path is excluded by the patterns passed with `--exclude`,
+/// relative to `root`.
+pub(crate) fn is_excluded(root: &Path, path: &Path, excludes: &[String]) -> bool {
+    let mut rules = IgnoreRules::new(root);
+    for exclude in excludes {
+        rules.add(exclude);
+    }
+    rules.matched(path, path.is_dir()).unwrap_or(false)
+}
+
+/// Whether a path or any of its parent directories inside `root` is excluded
+/// by patterns relative to `root`, so the crates inside excluded directories
+/// are excluded too.
+pub(crate) fn is_excluded_inside(root: &Path, path: &Path, excludes: &[String]) -> bool {
+    let root = normalize_path(&std::path::absolute(root).unwrap_or_else(|_| root.to_path_buf()));
+    let path = normalize_path(&std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()));
+    path.ancestors()
+        .take_while(|dir| *dir != root && dir.starts_with(&root))
+        .any(|dir| is_excluded(&root, dir, excludes))
+}
+
+/// Directories of the crates matched by a glob relative to `root`, like
+/// `crates/*` in the `members` of a workspace. Directories without a
+/// `Cargo.toml` are ignored, like Cargo does.
+#[cfg(feature = "workspace")]
+fn glob_crate_directories(root: &Path, pattern: &str) -> Vec<PathBuf> {
+    fn walk(dir: &Path, relative: &str, depth: usize, pattern: &str, matched: &mut Vec<PathBuf>) {
+        if depth == 0 {
+            return;
+        }
+        for entry_path in sorted_entries(dir) {
+            if !entry_path.is_dir() || is_skipped_directory(&entry_path) {
+                continue;
+            }
+            let name = entry_path.file_name().unwrap_or_default().to_string_lossy();
+            let entry_relative = if relative.is_empty() {
+                name.to_string()
+            } else {
+                format!("{relative}/{name}")
+            };
+            if glob::matches(pattern, &entry_relative) && entry_path.join("Cargo.toml").is_file() {
+                matched.push(entry_path.clone());
+            }
+            walk(&entry_path, &entry_relative, depth - 1, pattern, matched);
+        }
+    }
+
+    let components = pattern.split('/').collect::<Vec<_>>();
+    let literal_prefix = components
+        .iter()
+        .take_while(|component| !glob::is_glob(component))
+        .copied()
+        .collect::<Vec<_>>();
+    let depth = if pattern.contains("**") {
+        usize::MAX
+    } else {
+        components.len() - literal_prefix.len()
+    };
+    let base = literal_prefix
+        .iter()
+        .fold(root.to_path_buf(), |base, component| base.join(component));
+
+    let mut matched = Vec::new();
+    walk(
+        &base,
+        &literal_prefix.join("/"),
+        depth,
+        pattern,
+        &mut matched,
+    );
+    matched
+}
+
+/// Expand the `members` of a workspace to the directories of its crates.
+///
+/// Globs are expanded like Cargo does and the crates matched by them that
+/// are inside a directory of `exclude` are removed. Members listed without
+/// a glob are always included. A glob that doesn't match any crate is
+/// returned as error.
+#[cfg(feature = "workspace")]
+pub(crate) fn expand_workspace_members(
+    root: &Path,
+    members: &[&str],
+    exclude: &[&str],
+) -> Result<Vec<PathBuf>, String> {
+    let exclude = exclude
+        .iter()
+        .map(|excluded| root.join(excluded.trim_start_matches("./")))
+        .collect::<Vec<_>>();
+
+    let mut paths = Vec::new();
+    for member in members {
+        let member = member.trim_start_matches("./").trim_end_matches('/');
+        let member_paths = if glob::is_glob(member) {
+            let matched = glob_crate_directories(root, member);
+            if matched.is_empty() {
+                return Err(member.to_string());
+            }
+            matched
+                .into_iter()
+                .filter(|path| !exclude.iter().any(|excluded| path.starts_with(excluded)))
+                .collect()
+        } else {
+            vec![root.join(member)]
+        };
+        for member_path in member_paths {
+            if !paths.contains(&member_path) {
+                paths.push(member_path);
+            }
+        }
+    }
+    Ok(paths)
+}
+
+/// Parse the `Cargo.toml` of a directory.
+pub(crate) fn read_manifest(dir: &Path) -> Option<toml::Value> {
+    let content = std::fs::read_to_string(dir.join("Cargo.toml")).ok()?;
+    toml::de::from_str(&content).ok()
+}
+
+/// Get an array of strings of the `[workspace]` table of a manifest.
+#[cfg(feature = "workspace")]
+pub(crate) fn workspace_strings<'a>(manifest: &'a toml::Value, key: &str) -> Vec<&'a str> {
+    manifest
+        .get("workspace")
+        .and_then(|v| v.get(key))
+        .and_then(|v| v.as_array())
+        .map(|values| values.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_default()
+}
+
+/// Remove the `.` and `..` components of a path without accessing the
+/// filesystem.
+pub(crate) fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Whether a crate or directory is one of the workspace members or is
+/// inside one of them.
+pub(crate) fn is_in_member(path: &Path, members: &[PathBuf]) -> bool {
+    members.iter().any(|member| {
+        let member = normalize_path(member);
+        member.starts_with(path) || path.starts_with(&member)
+    })
+}
+
+/// Find the root directory of the workspace that contains `path`, like
+/// Cargo does.
+///
+/// The `package.workspace` field of the manifest at `path` is followed if
+/// present. Otherwise, the closest parent directory with a `[workspace]`
+/// manifest is taken, as long as `path` is the root of that workspace, one
+/// of its members or a directory containing members.
+#[cfg(feature = "workspace")]
+pub(crate) fn find_workspace_root(path: &Path) -> Option<PathBuf> {
+    let path = normalize_path(&std::path::absolute(path).ok()?);
+    let pointer = read_manifest(&path).and_then(|manifest| {
+        manifest
+            .get("package")?
+            .get("workspace")?
+            .as_str()
+            .map(|pointer| normalize_path(&path.join(pointer)))
+    });
+    if let Some(root) = pointer {
+        return read_manifest(&root)
+            .is_some_and(|manifest| manifest.get("workspace").is_some())
+            .then_some(root);
+    }
+
+    let (root, manifest) = path.ancestors().find_map(|dir| {
+        read_manifest(dir)
+            .filter(|manifest| manifest.get("workspace").is_some())
+            .map(|manifest| (dir.to_path_buf(), manifest))
+    })?;
+    if root == path {
+        return Some(root);
+    }
+    match expand_workspace_members(
+        &root,
+        &workspace_strings(&manifest, "members"),
+        &workspace_strings(&manifest, "exclude"),
+    ) {
+        Ok(members) if !is_in_member(&path, &members) => None,
+        // Errors in the members are reported when testing the workspace
+        _ => Some(root),
+    }
+}
+
+/// Get the root directory and the directories of the members of the
+/// workspace that contains `path` running `cargo metadata`.
+///
+/// Returns the error printed by `cargo` if it fails.
+pub(crate) fn cargo_metadata_members(path: &Path) -> Result<(PathBuf, Vec<PathBuf>), String> {
+    let output = std::process::Command::new("cargo")
+        .args(["metadata", "--no-deps", "--format-version", "1"])
+        .current_dir(path)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .map_err(|error| format!("Binary cargo could not be executed: {error}"))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+
+    let metadata = serde_json::from_slice::<serde_json::Value>(&output.stdout)
+        .map_err(|error| format!("The output of `cargo metadata` is not valid JSON: {error}"))?;
+    let workspace_root = metadata
+        .get("workspace_root")
+        .and_then(serde_json::Value::as_str)
+        .map(PathBuf::from)
+        .ok_or_else(|| "The output of `cargo metadata` has no workspace root".to_string())?;
+    // Without dependencies, the packages are the members of the workspace
+    let mut members = metadata
+        .get("packages")
+        .and_then(serde_json::Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|package| package.get("manifest_path")?.as_str())
+        .filter_map(|manifest_path| Path::new(manifest_path).parent().map(Path::to_path_buf))
+        .collect::<Vec<_>>();
+    members.sort();
+    Ok((workspace_root, members))
+}
+
+/// Get the name of the package of a crate from its `Cargo.toml`.
+pub(crate) fn package_name(crate_path: &Path) -> Option<String> {
+    read_manifest(crate_path)?
+        .get("package")?
+        .get("name")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Entries of a directory sorted by path, so discovery is deterministic.
+fn sorted_entries(path: &Path) -> Vec<PathBuf> {
+    let mut entries = std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    entries.sort();
+    entries
+}
+
+/// Discover the testable crates inside a directory and its subdirectories.
+///
+/// Paths matched by the patterns of `.gitignore`, `.ignore` and
+/// `.wasm-pack-test-all-ignore` files, or by `excludes`, are skipped.
+pub(crate) fn gather_crates_paths_in_dir_or_subdirs(
+    path: &Path,
+    excludes: &[String],
+) -> Vec<PathBuf> {
+    let mut ignores = Ignores::new(path, excludes);
+    gather_crates_paths(path, &mut ignores)
+}
+
+fn gather_crates_paths(path: &Path, ignores: &mut Ignores) -> Vec<PathBuf> {
+    let ignore_rules = IgnoreRules::from_dir(path);
+    let has_ignore_rules = ignore_rules.is_some();
+    ignores.files.extend(ignore_rules);
+
+    let mut paths = Vec::new();
+    for entry_path in sorted_entries(path) {
+        let is_dir = entry_path.is_dir();
+        if ignores.is_ignored(&entry_path, is_dir) {
+            continue;
+        }
+        if is_dir {
+            if !is_skipped_directory(&entry_path) {
+                paths.extend(gather_crates_paths(&entry_path, ignores));
+            }
+        } else if entry_path.file_name() == Some(std::ffi::OsStr::new("Cargo.toml")) {
+            if let Some(parent) = entry_path.parent() {
+                let new_path = parent.to_path_buf();
+                if is_testable_crate(&new_path) {
+                    paths.push(new_path.clone());
+                }
+            }
+        }
+    }
+
+    if has_ignore_rules {
+        ignores.files.pop();
+    }
+    paths
+}
+
+/// Scan the Rust files of a crate until `scan` returns a value for one of
+/// them.
+///
+/// Nested crates are not part of the crate, so their files are not scanned.
+fn scan_crate_files<T>(crate_path: &Path, scan: &impl Fn(&str) -> Option<T>) -> Option<T> {
+    for entry_path in sorted_entries(crate_path) {
+        if entry_path.is_dir() {
+            if !is_skipped_directory(&entry_path) && !entry_path.join("Cargo.toml").is_file() {
+                if let Some(found) = scan_crate_files(&entry_path, scan) {
+                    return Some(found);
+                }
+            }
+        } else if entry_path.extension() == Some(std::ffi::OsStr::new("rs")) {
+            let content = std::fs::read_to_string(&entry_path).unwrap_or_default();
+            if let Some(found) = scan(&content) {
+                return Some(found);
+            }
+        }
+    }
+
+    None
+}
+
+/// Whether a crate contains tests marked with `#[wasm_bindgen_test]`.
+pub(crate) fn is_testable_crate(crate_path: &Path) -> bool {
+    scan_crate_files(crate_path, &|content| {
+        scan::has_wasm_bindgen_test(content).then_some(())
+    })
+    .is_some()
+}
+
+/// Environment where the tests of a crate run, configured with
+/// `wasm_bindgen_test_configure!` in any of its files.
+pub(crate) fn crate_test_environment(crate_path: &Path) -> Option<scan::TestEnvironment> {
+    scan_crate_files(crate_path, &scan::configured_test_environment)
+}
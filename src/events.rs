@@ -0,0 +1,35 @@
+//! Machine-readable events printed to stdout as newline-delimited JSON when
+//! `--message-format json` is passed.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::json::Json;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Enable the events stream. Human messages are printed to stderr from now on.
+pub(crate) fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether the events stream is enabled.
+pub(crate) fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Print an event with the given name and fields, if events are enabled.
+pub(crate) fn emit(event: &str, fields: Vec<(&str, Json)>) {
+    if !enabled() {
+        return;
+    }
+    let mut line = String::from("{\"event\":");
+    line.push_str(&Json::from(event).to_string());
+    for (key, value) in fields {
+        line.push_str(&format!(",{}:{}", Json::from(key), value));
+    }
+    line.push('}');
+    #[allow(clippy::print_stdout)]
+    {
+        println!("{line}");
+    }
+}
@@ -0,0 +1,105 @@
+//! Glob patterns like the ones used by `.gitignore` files and Cargo.
+//!
+//! - `*` matches any sequence of characters except `/`.
+//! - `?` matches any character except `/`.
+//! - `[abc]`, `[a-z]` and `[!abc]` match a character of a set.
+//! - `**` matches any sequence of characters, including `/`. When it is a
+//!   complete path component, like in `**/foo` or `foo/**/bar`, it matches
+//!   zero or more directories.
+//! - `\` escapes the next character.
+
+/// Whether a string contains characters with special meaning in globs.
+#[cfg(feature = "workspace")]
+pub(crate) fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '[', '\\'])
+}
+
+/// Match a character class starting after its `[`, returning whether the
+/// class matched `c` and the index in `pattern` after the closing `]`.
+fn match_class(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    let mut i = 0;
+    let negated = matches!(pattern.first(), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    while i < pattern.len() {
+        if pattern[i] == ']' && !first {
+            return Some((matched != negated, i + 1));
+        }
+        first = false;
+        let start = pattern[i];
+        if i + 2 < pattern.len() && pattern[i + 1] == '-' && pattern[i + 2] != ']' {
+            if (start..=pattern[i + 2]).contains(&c) {
+                matched = true;
+            }
+            i += 3;
+        } else {
+            if start == c {
+                matched = true;
+            }
+            i += 1;
+        }
+    }
+    // Unclosed class
+    None
+}
+
+fn matches_chars(pattern: &[char], text: &[char]) -> bool {
+    let Some(&p) = pattern.first() else {
+        return text.is_empty();
+    };
+    match p {
+        '*' if pattern.get(1) == Some(&'*') => {
+            let rest = &pattern[2..];
+            if rest.first() == Some(&'/') {
+                // `**/` matches zero or more directories
+                let rest = &rest[1..];
+                if matches_chars(rest, text) {
+                    return true;
+                }
+                (0..text.len())
+                    .filter(|i| text[*i] == '/')
+                    .any(|i| matches_chars(rest, &text[i + 1..]))
+            } else {
+                (0..=text.len()).any(|i| matches_chars(rest, &text[i..]))
+            }
+        }
+        '*' => {
+            let rest = &pattern[1..];
+            for i in 0..=text.len() {
+                if matches_chars(rest, &text[i..]) {
+                    return true;
+                }
+                if i < text.len() && text[i] == '/' {
+                    break;
+                }
+            }
+            false
+        }
+        '?' => !text.is_empty() && text[0] != '/' && matches_chars(&pattern[1..], &text[1..]),
+        '[' => match (
+            text.first(),
+            match_class(&pattern[1..], *text.first().unwrap_or(&'/')),
+        ) {
+            (Some(c), Some((matched, end))) => {
+                *c != '/' && matched && matches_chars(&pattern[1 + end..], &text[1..])
+            }
+            // An unclosed `[` is matched literally
+            (Some('['), None) => matches_chars(&pattern[1..], &text[1..]),
+            _ => false,
+        },
+        '\\' if pattern.len() > 1 => {
+            text.first() == Some(&pattern[1]) && matches_chars(&pattern[2..], &text[1..])
+        }
+        p => text.first() == Some(&p) && matches_chars(&pattern[1..], &text[1..]),
+    }
+}
+
+/// Whether `text` matches the glob `pattern` completely.
+pub(crate) fn matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    matches_chars(&pattern, &text)
+}
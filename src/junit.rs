@@ -0,0 +1,169 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::output::{strip_ansi_codes, TestOutcome};
+use crate::runner::{CrateRun, CrateStatus};
+use crate::summary::format_duration;
+
+/// Escape a string to be included in XML text or attribute values.
+///
+/// Characters not allowed in XML 1.0 documents are removed.
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in strip_ansi_codes(text).chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c < ' ' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn format_seconds(duration: std::time::Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64())
+}
+
+/// Build a JUnit XML report with a `<testsuite>` for each crate, or for
+/// each crate and runtime when testing a matrix of runtimes.
+///
+/// When the tests of a crate can't be found in its output, like when the
+/// crate fails to compile, a single `<testcase>` for the whole crate is
+/// reported instead. The same happens when the crate failed but none of
+/// its tests did, like when it times out.
+///
+/// The time of the `<testsuites>` element is the elapsed `duration` of the
+/// whole run, not the sum of the times of the crates run in parallel.
+pub(crate) fn build_report(
+    root: &Path,
+    crate_runs: &[CrateRun],
+    duration: std::time::Duration,
+) -> String {
+    let mut suites = String::new();
+    let (mut total_tests, mut total_failures, mut total_skipped) = (0, 0, 0);
+
+    for crate_run in crate_runs {
+        let name = escape_xml(&crate_run.display_name(root));
+        let mut cases = String::new();
+        let (mut tests, mut failures, mut skipped) = (0, 0, 0);
+
+        let test_case_failed = crate_run
+            .test_cases
+            .iter()
+            .any(|test_case| test_case.outcome == TestOutcome::Failed);
+        if crate_run.test_cases.is_empty() || (crate_run.status.is_failure() && !test_case_failed) {
+            tests += 1;
+            let _ = write!(cases, r#"    <testcase name="{name}" classname="{name}""#);
+            match crate_run.status {
+                CrateStatus::Passed | CrateStatus::Flaky => cases.push_str("/>\n"),
+                CrateStatus::Failed | CrateStatus::TimedOut => {
+                    failures += 1;
+                    let message = match crate_run.exit_code {
+                        _ if crate_run.status == CrateStatus::TimedOut => format!(
+                            "`wasm-pack test` timed out after {}",
+                            format_duration(crate_run.duration)
+                        ),
+                        Some(code) => format!("`wasm-pack test` exited with code {code}"),
+                        None => "`wasm-pack test` did not exit normally".to_string(),
+                    };
+                    let _ = writeln!(
+                        cases,
+                        r#">
+      <failure message="{}"/>
+    </testcase>"#,
+                        escape_xml(&message)
+                    );
+                }
+                CrateStatus::Cancelled | CrateStatus::Skipped => {
+                    skipped += 1;
+                    let _ = writeln!(
+                        cases,
+                        r#">
+      <skipped message="{}"/>
+    </testcase>"#,
+                        crate_run.status.as_str()
+                    );
+                }
+            }
+        }
+
+        for test_case in &crate_run.test_cases {
+            tests += 1;
+            let _ = write!(
+                cases,
+                r#"    <testcase name="{}" classname="{name}""#,
+                escape_xml(&test_case.name)
+            );
+            match test_case.outcome {
+                TestOutcome::Passed => cases.push_str("/>\n"),
+                TestOutcome::Ignored => {
+                    skipped += 1;
+                    cases.push_str(">\n      <skipped/>\n    </testcase>\n");
+                }
+                TestOutcome::Failed => {
+                    failures += 1;
+                    let _ = writeln!(
+                        cases,
+                        r#">
+      <failure message="test failed">{}</failure>
+    </testcase>"#,
+                        escape_xml(&test_case.failure_output)
+                    );
+                }
+            }
+        }
+
+        let mut properties = Vec::new();
+        if let Some(runtime) = &crate_run.runtime {
+            properties.push(("runtime", runtime.clone()));
+        }
+        if crate_run.attempts > 1 {
+            properties.push(("attempts", crate_run.attempts.to_string()));
+            properties.push((
+                "flaky",
+                (crate_run.status == CrateStatus::Flaky).to_string(),
+            ));
+        }
+        let properties = if properties.is_empty() {
+            String::new()
+        } else {
+            let mut properties_xml = "    <properties>\n".to_string();
+            for (property, value) in properties {
+                let _ = writeln!(
+                    properties_xml,
+                    r#"      <property name="{property}" value="{}"/>"#,
+                    escape_xml(&value)
+                );
+            }
+            properties_xml.push_str("    </properties>\n");
+            properties_xml
+        };
+        let _ = writeln!(
+            suites,
+            r#"  <testsuite name="{name}" tests="{tests}" failures="{failures}" errors="0" skipped="{skipped}" time="{}">
+{properties}{cases}    <system-out>{}</system-out>
+    <system-err>{}</system-err>
+  </testsuite>"#,
+            format_seconds(crate_run.duration),
+            escape_xml(&crate_run.stdout),
+            escape_xml(&crate_run.stderr),
+        );
+
+        total_tests += tests;
+        total_failures += failures;
+        total_skipped += skipped;
+    }
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="wasm-pack-test-all" tests="{total_tests}" failures="{total_failures}" errors="0" skipped="{total_skipped}" time="{}">
+{suites}</testsuites>
+"#,
+        format_seconds(duration)
+    )
+}
@@ -1,3 +1,34 @@
+macro_rules! print_to_stderr {
+    ($($arg:tt)*) => {{
+        eprintln!("[wasm-pack-test-all] {}", format!($($arg)*));
+    }};
+}
+
+macro_rules! print_to_stdout {
+    ($($arg:tt)*) => {{
+        // stdout is reserved to events when they are enabled
+        if crate::events::enabled() {
+            eprintln!("[wasm-pack-test-all] {}", format!($($arg)*));
+        } else {
+            #[allow(clippy::print_stdout)]
+            {
+                println!("[wasm-pack-test-all] {}", format!($($arg)*));
+            }
+        }
+    }};
+}
+
+mod changed;
+mod config;
+mod discovery;
+mod events;
+mod glob;
+mod json;
+mod junit;
+mod output;
+mod runner;
+mod scan;
+mod summary;
 #[cfg(test)]
 mod tests;
 
@@ -18,6 +49,8 @@ enum ExitCode {
     NoTestsFound = 5,
     TestsFailed = 6,
     ExternalError = 7,
+    InvalidOption = 8,
+    TimedOut = 9,
 }
 
 impl PartialEq for ExitCode {
@@ -30,7 +63,7 @@ fn print_help() {
     eprintln!(
         r#"Wrapper for `wasm-pack test` that runs tests for all crates in a workspace or directory.
 
-wasm-pack-test-all [-h/--help] [-V/--version] [PATH] [WASM_PACK_TEST_OPTIONS] [-- EXTRA_OPTIONS]
+wasm-pack-test-all [-h/--help] [-V/--version] [-j/--jobs N] [--junit FILE] [--message-format FORMAT] [--list] [--fail-fast] [--timeout DURATION] [--retries N] [--exclude GLOB] [--workspace] [--discovery MODE] [-p/--package SPEC] [--exclude-package SPEC] [--changed-since REF] [--files [--with-dependents]] [--profile NAME] [--print-config] [--auto-runtime] [--runtimes LIST] [PATH] [WASM_PACK_TEST_OPTIONS] [-- EXTRA_OPTIONS] [FILES]
 
 Arguments:
   [PATH]
@@ -50,6 +83,99 @@ Options:
 
   -V, --version
           Print version.
+
+  -j, --jobs <N>
+          Number of crates to test in parallel. When greater than 1, the output of each crate
+          is prefixed with its path and gets its own test server port. [default: 1]
+
+  --junit <FILE>
+          Write a JUnit XML report with a test suite for each crate to FILE.
+
+  --message-format <FORMAT>
+          Format of the messages printed to stdout, `human` or `json`. With `json`, events are
+          printed to stdout as newline-delimited JSON and all other output goes to stderr.
+          [default: human]
+
+  --list, --dry-run
+          Print the crates that would be tested and the `wasm-pack test` commands that would
+          be executed for them, without executing them.
+
+  --fail-fast, --no-fail-fast
+          Whether to stop testing crates after the first one that fails. Running crates are
+          killed and the remaining ones are skipped. [default: --no-fail-fast]
+
+  --timeout <DURATION>
+          Maximum time that testing each crate can take, like `90s`, `5m` or `1h30m`. When
+          exceeded, `wasm-pack` and all the processes spawned by it are killed, and the crate
+          is reported as timed out.
+
+  --total-timeout <DURATION>
+          Maximum time that testing all crates can take. When exceeded, running crates are
+          killed and reported as timed out, and the remaining ones are skipped.
+
+  --retries <N>
+          Number of times that a crate that fails or times out is tested again. Crates that
+          pass when retried are reported as flaky. [default: 0]
+
+  --fail-on-flaky
+          Make the run fail when a crate is flaky.
+
+  --exclude <GLOB>
+          Skip the paths matching a pattern during discovery, with the syntax of `.gitignore`
+          files and relative to PATH. Can be passed multiple times. Paths matched by the
+          patterns of `.gitignore`, `.ignore` and `.wasm-pack-test-all-ignore` files are
+          skipped too.
+
+  --workspace
+          Test all the members of the workspace that contains PATH. By default, when PATH is
+          inside a workspace only the members at PATH are tested. Requires the `workspace`
+          feature, unless `--discovery cargo-metadata` is passed.
+
+  --discovery <MODE>
+          How to discover the crates to test. `filesystem` walks PATH and reads the manifests
+          of workspaces. `cargo-metadata` tests the members of the workspace reported by
+          `cargo metadata`. [default: filesystem]
+
+  -p, --package <SPEC>
+          Only test the crates which package name matches SPEC, which can be a glob like
+          `app-*`. Can be passed multiple times. Fails if SPEC doesn't match any discovered
+          crate.
+
+  --exclude-package <SPEC>
+          Don't test the crates which package name matches SPEC, which can be a glob. Can be
+          passed multiple times.
+
+  --changed-since <REF>
+          Only test the crates with files changed since the Git reference REF, including
+          uncommitted changes and untracked files, and the crates that depend on them
+          through `path` dependencies.
+
+  --files
+          Only test the crates that own the FILES passed as arguments, which can be placed
+          anywhere, like at the end by pre-commit. Arguments that are existing files are taken
+          as FILES, except the values of options like `--chromedriver` or `--config`.
+
+  --with-dependents
+          With `--files`, also test the crates that depend on the crates owning the FILES
+          through `path` dependencies.
+
+  --profile <NAME>
+          Use the settings of the `[profile.NAME]` table of the `wasm-pack-test-all.toml`
+          file, which is searched in PATH and its parent directories.
+
+  --print-config
+          Print the settings that result from merging the options passed with the
+          configuration files as TOML, without testing any crate.
+
+  --auto-runtime
+          Select the runtime of each crate from its sources. Crates that call
+          `wasm_bindgen_test_configure!` with `run_in_browser` or a worker are tested in the
+          browsers selected, or headless Chrome if none is selected, and the rest with
+          `--node`.
+
+  --runtimes <LIST>
+          Test each crate once in each runtime of the comma-separated LIST, like
+          `node,chrome,firefox`, and report the results as a crate by runtime matrix.
 "#
     );
 }
@@ -59,24 +185,11 @@ fn print_version() {
     println!("wasm-pack-test-all {}", env!("CARGO_PKG_VERSION"));
 }
 
-macro_rules! print_to_stderr {
-    ($($arg:tt)*) => {{
-        eprintln!("[wasm-pack-test-all] {}", format!($($arg)*));
-    }};
-}
-
-macro_rules! print_to_stdout {
-    ($($arg:tt)*) => {{
-        #[allow(clippy::print_stdout)]
-        {
-            println!("[wasm-pack-test-all] {}", format!($($arg)*));
-        }
-    }};
-}
-
 macro_rules! gather_crate_paths {
-    ($path:ident) => {{
-        let crates = gather_crates_paths_in_dir_or_subdirs(&$path);
+    ($path:ident, $options:expr) => {{
+        let mut crates =
+            discovery::gather_crates_paths_in_dir_or_subdirs(&$path, &$options.excludes);
+        crates.retain(|crate_path| !is_excluded_by_defaults(crate_path, &$options));
         if crates.is_empty() {
             print_to_stderr!("No crates found in the directory {}.", &$path.display());
             return ExitCode::NoCratesFound;
@@ -90,17 +203,264 @@ macro_rules! gather_crate_paths {
     }};
 }
 
-fn parse_options(args: &[String]) -> Result<(Option<String>, Vec<String>, Vec<String>), ExitCode> {
+/// Options parsed from the command line.
+struct Options {
+    path_argument: Option<String>,
+    wasm_pack_test_options: Vec<String>,
+    cargo_test_options: Vec<String>,
+    jobs: Option<usize>,
+    junit: Option<std::path::PathBuf>,
+    message_format_json: bool,
+    dry_run: bool,
+    fail_fast: bool,
+    timeout: Option<std::time::Duration>,
+    total_timeout: Option<std::time::Duration>,
+    retries: Option<usize>,
+    fail_on_flaky: bool,
+    excludes: Vec<String>,
+    /// Patterns of paths to skip read from the configuration files, with the
+    /// directory they are relative to.
+    default_excludes: Vec<(std::path::PathBuf, Vec<String>)>,
+    workspace: bool,
+    cargo_metadata_discovery: bool,
+    packages: Vec<String>,
+    excluded_packages: Vec<String>,
+    changed_since: Option<String>,
+    files_mode: bool,
+    files: Vec<String>,
+    with_dependents: bool,
+    profile: Option<String>,
+    print_config: bool,
+    auto_runtime: bool,
+    /// Runtimes in which each crate is tested, one run per runtime.
+    runtimes: Vec<String>,
+    /// Runtimes read from the configuration files, kept apart from the
+    /// options of the command line because the configuration of each crate
+    /// overrides them.
+    default_runtimes: Vec<String>,
+    /// Configuration file found walking up from the tested path.
+    config_file: Option<std::path::PathBuf>,
+}
+
+/// Get the value of a wasm-pack-test-all option that takes a value.
+///
+/// Supports both `--option value` and `--option=value` forms.
+fn option_value<'a>(
+    arg: &'a str,
+    names: &[&str],
+    args: &mut std::slice::Iter<'a, String>,
+) -> Option<Result<&'a str, ExitCode>> {
+    for name in names {
+        if arg == *name {
+            return Some(match args.next() {
+                Some(value) => Ok(value),
+                None => {
+                    print_to_stderr!("Missing value for option {}.", name);
+                    Err(ExitCode::InvalidOption)
+                }
+            });
+        } else if let Some(value) = arg
+            .strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix('='))
+        {
+            return Some(Ok(value));
+        }
+    }
+    None
+}
+
+fn parse_jobs(value: &str) -> Result<usize, ExitCode> {
+    match value.parse::<usize>() {
+        Ok(jobs) if jobs > 0 => Ok(jobs),
+        _ => {
+            print_to_stderr!(
+                "Invalid value for option --jobs (found {}). Expected a positive integer.",
+                value
+            );
+            Err(ExitCode::InvalidOption)
+        }
+    }
+}
+
+fn parse_duration(option: &str, value: &str) -> Result<std::time::Duration, ExitCode> {
+    config::parse_duration(value).ok_or_else(|| {
+        print_to_stderr!(
+            "Invalid value for option {} (found {}). Expected a duration like 90s, 5m or 1h30m.",
+            option,
+            value
+        );
+        ExitCode::InvalidOption
+    })
+}
+
+/// Options of `wasm-pack test` and `cargo test` whose value can be a file,
+/// which is never taken as a file to test with `--files`.
+const FILE_VALUE_OPTIONS: &[&str] = &[
+    "--chromedriver",
+    "--geckodriver",
+    "--safaridriver",
+    "--manifest-path",
+    "--config",
+];
+
+fn parse_options(args: &[String]) -> Result<Options, ExitCode> {
     let mut path_argument = None;
     let mut wasm_pack_test_options = Vec::new();
     let mut cargo_test_options = Vec::new();
+    let mut jobs = None;
+    let mut junit = None;
+    let mut message_format_json = false;
+    let mut dry_run = false;
+    let mut fail_fast = false;
+    let mut timeout = None;
+    let mut total_timeout = None;
+    let mut retries = None;
+    let mut fail_on_flaky = false;
+    let mut excludes = Vec::new();
+    let mut workspace = false;
+    let mut cargo_metadata_discovery = false;
+    let mut packages = Vec::new();
+    let mut excluded_packages = Vec::new();
+    let mut changed_since = None;
+    // With `--files`, existing files passed as arguments are taken as the
+    // files to test, like the ones passed by pre-commit at the end, except
+    // the values of options
+    let files_mode = args
+        .iter()
+        .take_while(|arg| *arg != "--")
+        .any(|arg| arg == "--files");
+    let mut files = Vec::new();
+    let mut with_dependents = false;
+    let mut profile = None;
+    let mut print_config = false;
+    let mut auto_runtime = false;
+    let mut runtimes = Vec::new();
 
     const INSIDE_WASM_PACK_TEST_ALL_OPTIONS: u8 = 1;
     const INSIDE_WASM_PACK_TEST_OPTIONS: u8 = 2;
     const INSIDE_CARGO_TEST_OPTIONS: u8 = 4;
     let mut state: u8 = INSIDE_WASM_PACK_TEST_ALL_OPTIONS;
 
-    for arg in args {
+    let mut args_iter = args.iter();
+    let mut previous_arg: Option<&String> = None;
+    while let Some(arg) = args_iter.next() {
+        let is_option_value =
+            previous_arg.is_some_and(|previous| FILE_VALUE_OPTIONS.contains(&previous.as_str()));
+        previous_arg = Some(arg);
+        if files_mode
+            && !is_option_value
+            && !arg.starts_with('-')
+            && std::path::Path::new(arg).is_file()
+        {
+            files.push(arg.to_string());
+            continue;
+        }
+        if state != INSIDE_CARGO_TEST_OPTIONS {
+            // wasm-pack-test-all options can be passed anywhere before `--`
+            if arg == "--list" || arg == "--dry-run" {
+                dry_run = true;
+                continue;
+            } else if arg == "--files" {
+                continue;
+            } else if arg == "--with-dependents" {
+                with_dependents = true;
+                continue;
+            } else if arg == "--workspace" {
+                workspace = true;
+                continue;
+            } else if arg == "--print-config" {
+                print_config = true;
+                continue;
+            } else if arg == "--auto-runtime" {
+                auto_runtime = true;
+                continue;
+            } else if arg == "--fail-on-flaky" {
+                fail_on_flaky = true;
+                continue;
+            } else if arg == "--fail-fast" || arg == "--no-fail-fast" {
+                fail_fast = arg == "--fail-fast";
+                continue;
+            } else if let Some(value) = option_value(arg, &["--jobs", "-j"], &mut args_iter) {
+                jobs = Some(parse_jobs(value?)?);
+                continue;
+            } else if let Some(value) = option_value(arg, &["--retries"], &mut args_iter) {
+                let value = value?;
+                retries = Some(value.parse::<usize>().map_err(|_| {
+                    print_to_stderr!(
+                        "Invalid value for option --retries (found {}). Expected a non-negative integer.",
+                        value
+                    );
+                    ExitCode::InvalidOption
+                })?);
+                continue;
+            } else if let Some(value) = option_value(arg, &["--timeout"], &mut args_iter) {
+                timeout = Some(parse_duration("--timeout", value?)?);
+                continue;
+            } else if let Some(value) = option_value(arg, &["--total-timeout"], &mut args_iter) {
+                total_timeout = Some(parse_duration("--total-timeout", value?)?);
+                continue;
+            } else if let Some(value) = option_value(arg, &["--package", "-p"], &mut args_iter) {
+                packages.push(value?.to_string());
+                continue;
+            } else if let Some(value) = option_value(arg, &["--exclude-package"], &mut args_iter) {
+                excluded_packages.push(value?.to_string());
+                continue;
+            } else if let Some(value) = option_value(arg, &["--changed-since"], &mut args_iter) {
+                changed_since = Some(value?.to_string());
+                continue;
+            } else if let Some(value) = option_value(arg, &["--runtimes"], &mut args_iter) {
+                for runtime in value?.split(',') {
+                    if !config::RUNTIMES.contains(&runtime) {
+                        print_to_stderr!(
+                            "Invalid value for option --runtimes (found {}). Expected a comma-separated list of {}.",
+                            runtime,
+                            config::RUNTIMES.join(", ")
+                        );
+                        return Err(ExitCode::InvalidOption);
+                    }
+                    if !runtimes.iter().any(|selected| selected == runtime) {
+                        runtimes.push(runtime.to_string());
+                    }
+                }
+                continue;
+            } else if let Some(value) = option_value(arg, &["--profile"], &mut args_iter) {
+                profile = Some(value?.to_string());
+                continue;
+            } else if let Some(value) = option_value(arg, &["--exclude"], &mut args_iter) {
+                excludes.push(value?.to_string());
+                continue;
+            } else if let Some(value) = option_value(arg, &["--junit"], &mut args_iter) {
+                junit = Some(std::path::PathBuf::from(value?));
+                continue;
+            } else if let Some(value) = option_value(arg, &["--discovery"], &mut args_iter) {
+                cargo_metadata_discovery = match value? {
+                    "filesystem" => false,
+                    "cargo-metadata" => true,
+                    value => {
+                        print_to_stderr!(
+                            "Invalid value for option --discovery (found {}). Expected `filesystem` or `cargo-metadata`.",
+                            value
+                        );
+                        return Err(ExitCode::InvalidOption);
+                    }
+                };
+                continue;
+            } else if let Some(value) = option_value(arg, &["--message-format"], &mut args_iter) {
+                message_format_json = match value? {
+                    "human" => false,
+                    "json" => true,
+                    value => {
+                        print_to_stderr!(
+                            "Invalid value for option --message-format (found {}). Expected `human` or `json`.",
+                            value
+                        );
+                        return Err(ExitCode::InvalidOption);
+                    }
+                };
+                continue;
+            }
+        }
+
         if state == INSIDE_WASM_PACK_TEST_ALL_OPTIONS {
             if arg == "--" {
                 state <<= 2;
@@ -134,7 +494,512 @@ fn parse_options(args: &[String]) -> Result<(Option<String>, Vec<String>, Vec<St
         }
     }
 
-    Ok((path_argument, wasm_pack_test_options, cargo_test_options))
+    Ok(Options {
+        path_argument,
+        wasm_pack_test_options,
+        cargo_test_options,
+        jobs,
+        junit,
+        message_format_json,
+        dry_run,
+        fail_fast,
+        timeout,
+        total_timeout,
+        retries,
+        fail_on_flaky,
+        excludes,
+        default_excludes: Vec::new(),
+        workspace,
+        cargo_metadata_discovery,
+        packages,
+        excluded_packages,
+        changed_since,
+        files_mode,
+        files,
+        with_dependents,
+        profile,
+        print_config,
+        auto_runtime,
+        runtimes,
+        default_runtimes: Vec::new(),
+        config_file: None,
+    })
+}
+
+/// Keep the members of a workspace that are at `path`, or all of them with
+/// `--workspace`, removing the ones excluded with `--exclude` or by the
+/// configuration files.
+fn members_at_path(
+    path: &std::path::Path,
+    workspace_root: &std::path::Path,
+    members: Vec<std::path::PathBuf>,
+    options: &Options,
+) -> Vec<std::path::PathBuf> {
+    let path_absolute = discovery::normalize_path(
+        &std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+    );
+    members
+        .into_iter()
+        .filter(|member| {
+            options.workspace
+                || workspace_root == path
+                || discovery::is_in_member(&path_absolute, std::slice::from_ref(member))
+        })
+        .filter(|member| !discovery::is_excluded_inside(path, member, &options.excludes))
+        .filter(|member| !is_excluded_by_defaults(member, options))
+        .collect()
+}
+
+/// Whether a crate is excluded by the patterns of the configuration files.
+fn is_excluded_by_defaults(crate_path: &std::path::Path, options: &Options) -> bool {
+    options
+        .default_excludes
+        .iter()
+        .any(|(root, excludes)| discovery::is_excluded_inside(root, crate_path, excludes))
+}
+
+/// Add default options, which go before the ones passed in the command
+/// line. The default runtimes are kept apart until the runtimes of each
+/// crate are selected, and the first configuration that defines them wins.
+fn prepend_default_args(
+    options: &mut Options,
+    runtimes: Vec<String>,
+    wasm_pack_args: Vec<String>,
+    cargo_args: Vec<String>,
+) {
+    if options.default_runtimes.is_empty() {
+        options.default_runtimes = runtimes;
+    }
+    options.wasm_pack_test_options = wasm_pack_args
+        .into_iter()
+        .chain(std::mem::take(&mut options.wasm_pack_test_options))
+        .collect();
+
+    // The default options of cargo go right after `--`, before the ones
+    // passed in the command line
+    if !cargo_args.is_empty() {
+        options.cargo_test_options = std::iter::once("--".to_string())
+            .chain(cargo_args)
+            .chain(options.cargo_test_options.iter().skip(1).cloned())
+            .collect();
+    }
+}
+
+/// Merge the settings of the configuration file into the options passed in
+/// the command line, which take precedence.
+fn apply_file_settings(
+    options: &mut Options,
+    config_file: std::path::PathBuf,
+    settings: config::Settings,
+) {
+    options.jobs = options.jobs.or(settings.jobs);
+    options.retries = options.retries.or(settings.retries);
+    options.timeout = options.timeout.or(settings.timeout);
+    options.junit = options.junit.take().or(settings.junit);
+    if options.packages.is_empty() {
+        options.packages = settings.packages.unwrap_or_default();
+    }
+    if options.excluded_packages.is_empty() {
+        options.excluded_packages = settings.exclude_packages.unwrap_or_default();
+    }
+    if let Some(exclude) = settings.exclude {
+        let root = config_file.parent().unwrap_or(std::path::Path::new(""));
+        options.default_excludes.push((root.to_path_buf(), exclude));
+    }
+    prepend_default_args(
+        options,
+        settings.runtimes.unwrap_or_default(),
+        settings.wasm_pack_args.unwrap_or_default(),
+        settings.cargo_args.unwrap_or_default(),
+    );
+    options.config_file = Some(config_file);
+}
+
+/// Merge the defaults of the `[workspace.metadata.wasm-pack-test-all]` table
+/// into the options passed in the command line, which take precedence.
+#[cfg(feature = "workspace")]
+fn apply_workspace_defaults(
+    options: &mut Options,
+    workspace_root: &std::path::Path,
+    defaults: config::WorkspaceConfig,
+) {
+    options.jobs = options.jobs.or(defaults.jobs);
+    options.timeout = options.timeout.or(defaults.timeout);
+    if !defaults.exclude.is_empty() {
+        options
+            .default_excludes
+            .push((workspace_root.to_path_buf(), defaults.exclude));
+    }
+    prepend_default_args(
+        options,
+        defaults.runtimes,
+        defaults.wasm_pack_args,
+        defaults.cargo_args,
+    );
+}
+
+/// Print the settings that result from merging the options passed in the
+/// command line with the configuration files, in the format of the
+/// configuration file.
+///
+/// Exclude patterns are relative to different directories, so only the ones
+/// relative to the directory of the configuration file, or to `path` if there
+/// is none, are printed in `exclude`. The rest are printed as comments.
+#[allow(clippy::print_stdout)]
+fn print_config(options: &Options, path: &std::path::Path) {
+    let strings = |strings: &[String]| {
+        toml::Value::Array(strings.iter().cloned().map(toml::Value::String).collect())
+    };
+    let (runtimes, wasm_pack_args): (Vec<_>, Vec<_>) = options
+        .wasm_pack_test_options
+        .iter()
+        .cloned()
+        .partition(|option| config::runtime_of_option(option).is_some());
+    let mut runtimes = runtimes
+        .iter()
+        .filter_map(|option| config::runtime_of_option(option))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    if !options.runtimes.is_empty() {
+        runtimes.clone_from(&options.runtimes);
+    } else if runtimes.is_empty() {
+        runtimes.clone_from(&options.default_runtimes);
+    }
+    let absolute = |path: &std::path::Path| {
+        discovery::normalize_path(&std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()))
+    };
+    let base = absolute(
+        options
+            .config_file
+            .as_deref()
+            .and_then(std::path::Path::parent)
+            .unwrap_or(path),
+    );
+    let mut excludes = Vec::new();
+    let mut other_excludes: Vec<(std::path::PathBuf, Vec<String>)> = Vec::new();
+    for (root, root_excludes) in std::iter::once((path, &options.excludes)).chain(
+        options
+            .default_excludes
+            .iter()
+            .map(|(root, excludes)| (root.as_path(), excludes)),
+    ) {
+        let root = absolute(root);
+        if root == base {
+            excludes.extend(root_excludes.iter().cloned());
+        } else if !root_excludes.is_empty() {
+            match other_excludes.iter_mut().find(|(other, _)| *other == root) {
+                Some((_, other_excludes)) => other_excludes.extend(root_excludes.iter().cloned()),
+                None => other_excludes.push((root, root_excludes.clone())),
+            }
+        }
+    }
+    let cargo_args = options
+        .cargo_test_options
+        .iter()
+        .skip(1)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let mut settings = vec![
+        ("runtimes", strings(&runtimes)),
+        (
+            "jobs",
+            toml::Value::Integer(options.jobs.unwrap_or(1) as i64),
+        ),
+        (
+            "retries",
+            toml::Value::Integer(options.retries.unwrap_or(0) as i64),
+        ),
+    ];
+    if let Some(timeout) = options.timeout {
+        settings.push((
+            "timeout",
+            toml::Value::String(config::format_duration(timeout)),
+        ));
+    }
+    if let Some(junit) = &options.junit {
+        settings.push(("junit", toml::Value::String(junit.display().to_string())));
+    }
+    settings.extend([
+        ("exclude", strings(&excludes)),
+        ("packages", strings(&options.packages)),
+        ("exclude-packages", strings(&options.excluded_packages)),
+        ("wasm-pack-args", strings(&wasm_pack_args)),
+        ("cargo-args", strings(&cargo_args)),
+    ]);
+
+    // Plain TOML without the prefix of the messages, so it can be copied
+    // to a configuration file
+    match (&options.config_file, &options.profile) {
+        (Some(config_file), Some(profile)) => println!(
+            "# Configuration file: {} (profile {})",
+            config_file.display(),
+            profile
+        ),
+        (Some(config_file), None) => println!("# Configuration file: {}", config_file.display()),
+        (None, _) => println!("# No configuration file found"),
+    }
+    for (key, value) in settings {
+        println!("{} = {}", key, value);
+    }
+    for (root, excludes) in other_excludes {
+        println!(
+            "# Also excluded, relative to {}: {}",
+            root.display(),
+            strings(&excludes)
+        );
+    }
+}
+
+/// Skip the members of a workspace without wasm tests, like native-only or
+/// proc-macro crates, that would fail to build for wasm32.
+fn testable_members(
+    workspace_root: &std::path::Path,
+    members: Vec<std::path::PathBuf>,
+) -> Vec<std::path::PathBuf> {
+    let (members, skipped_members): (Vec<_>, Vec<_>) = members
+        .into_iter()
+        .partition(|member| discovery::is_testable_crate(member));
+    print_to_stdout!(
+        "Found {} crates in the workspace {}",
+        members.len(),
+        workspace_root.display()
+    );
+    for skipped_member in &skipped_members {
+        print_to_stdout!(
+            "  - {} (skipped: no wasm_bindgen_test found)",
+            runner::display_crate_path(workspace_root, skipped_member)
+        );
+    }
+    members
+}
+
+/// Discover the crates to test with the members of the workspace reported
+/// by `cargo metadata`.
+fn cargo_metadata_crates_paths(
+    path: &std::path::Path,
+    options: &Options,
+) -> Result<Vec<std::path::PathBuf>, ExitCode> {
+    let (workspace_root, members) = discovery::cargo_metadata_members(path).map_err(|error| {
+        print_to_stderr!(
+            "Failed to discover crates with `cargo metadata` in {}:\n{}",
+            path.display(),
+            error
+        );
+        ExitCode::ExternalError
+    })?;
+    // Paths reported by Cargo are canonical
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let members = members_at_path(&path, &workspace_root, members, options);
+    if members.is_empty() {
+        print_to_stderr!(
+            "No crates found in the workspace {}.",
+            workspace_root.display()
+        );
+        return Err(ExitCode::NoCratesFound);
+    }
+    Ok(testable_members(&workspace_root, members))
+}
+
+/// Keep the crates which package names match the patterns passed with
+/// `-p/--package` and don't match the ones passed with `--exclude-package`.
+///
+/// Fails if a pattern passed with `-p/--package` doesn't match any crate.
+fn select_packages(
+    crates_paths: Vec<std::path::PathBuf>,
+    options: &Options,
+) -> Result<Vec<std::path::PathBuf>, ExitCode> {
+    let crates_names = crates_paths
+        .into_iter()
+        .map(|crate_path| {
+            let name = discovery::package_name(&crate_path).unwrap_or_default();
+            (crate_path, name)
+        })
+        .collect::<Vec<_>>();
+
+    for package in &options.packages {
+        if !crates_names
+            .iter()
+            .any(|(_, name)| glob::matches(package, name))
+        {
+            print_to_stderr!(
+                "The package {} was not found in the discovered crates.",
+                package
+            );
+            return Err(ExitCode::NoCratesFound);
+        }
+    }
+
+    let total = crates_names.len();
+    let selected = crates_names
+        .into_iter()
+        .filter(|(_, name)| {
+            options.packages.is_empty()
+                || options
+                    .packages
+                    .iter()
+                    .any(|package| glob::matches(package, name))
+        })
+        .filter(|(_, name)| {
+            !options
+                .excluded_packages
+                .iter()
+                .any(|package| glob::matches(package, name))
+        })
+        .map(|(crate_path, _)| crate_path)
+        .collect::<Vec<_>>();
+    print_to_stdout!(
+        "Selected {} of {} crates by package name.",
+        selected.len(),
+        total
+    );
+    Ok(selected)
+}
+
+/// Keep the crates affected by the changes since a Git reference.
+fn changed_crates_paths(
+    path: &std::path::Path,
+    crates_paths: Vec<std::path::PathBuf>,
+    reference: &str,
+) -> Result<Vec<std::path::PathBuf>, ExitCode> {
+    let changed_files = changed::changed_files(path, reference).map_err(|error| {
+        print_to_stderr!(
+            "Failed to get the files changed since {}:\n{}",
+            reference,
+            error
+        );
+        ExitCode::ExternalError
+    })?;
+    let total = crates_paths.len();
+    let crates_paths = changed::affected_crates(crates_paths, &changed_files, true);
+    print_to_stdout!(
+        "Selected {} of {} crates with changes since {}.",
+        crates_paths.len(),
+        total,
+        reference
+    );
+    Ok(crates_paths)
+}
+
+/// Keep the crates that own the files passed with `--files`.
+fn files_crates_paths(
+    crates_paths: Vec<std::path::PathBuf>,
+    options: &Options,
+) -> Vec<std::path::PathBuf> {
+    let files = options
+        .files
+        .iter()
+        .map(std::path::PathBuf::from)
+        .collect::<Vec<_>>();
+    let total = crates_paths.len();
+    let crates_paths = changed::affected_crates(crates_paths, &files, options.with_dependents);
+    print_to_stdout!(
+        "Selected {} of {} crates owning the passed files.",
+        crates_paths.len(),
+        total
+    );
+    crates_paths
+}
+
+/// Build the invocations of `wasm-pack test` for the crates, merging the
+/// options passed in the command line with the configuration of each crate.
+///
+/// Crates that must not be tested are left out with a message.
+fn crate_invocations(
+    root: &std::path::Path,
+    crates_paths: Vec<std::path::PathBuf>,
+    options: &Options,
+) -> Result<Vec<runner::CrateInvocation>, ExitCode> {
+    let mut invocations = Vec::new();
+    for crate_path in crates_paths {
+        let crate_config = config::CrateConfig::from_crate(&crate_path).map_err(|error| {
+            print_to_stderr!(
+                "Invalid [package.metadata.wasm-pack-test-all] table in {}: {}.",
+                crate_path.join("Cargo.toml").display(),
+                error
+            );
+            ExitCode::InvalidOption
+        })?;
+        let environment = options
+            .auto_runtime
+            .then(|| discovery::crate_test_environment(&crate_path));
+        // The default runtimes are only used when no runtime is selected in
+        // the command line, and the configuration of the crate overrides them
+        let runtime_selected = options
+            .wasm_pack_test_options
+            .iter()
+            .any(|option| config::runtime_of_option(option).is_some());
+        let default_runtimes = if runtime_selected || !options.runtimes.is_empty() {
+            Vec::new()
+        } else {
+            crate_config.default_runtimes(&options.default_runtimes)
+        };
+        // Without a matrix of runtimes, each crate is tested once with the
+        // runtimes selected in the options. The default runtimes are a matrix
+        // too, except with `--auto-runtime`, which picks one of them
+        let runtimes = if !options.runtimes.is_empty() {
+            options
+                .runtimes
+                .iter()
+                .map(|runtime| Some(runtime.as_str()))
+                .collect()
+        } else if environment.is_none() && !default_runtimes.is_empty() {
+            default_runtimes
+                .iter()
+                .map(|runtime| Some(runtime.as_str()))
+                .collect()
+        } else {
+            vec![None]
+        };
+        for runtime in runtimes {
+            let display_name = runner::display_crate_run(root, &crate_path, runtime);
+            let wasm_pack_test_options = match (runtime, environment) {
+                (Some(runtime), Some(environment))
+                    if (runtime != "node") != environment.is_some() =>
+                {
+                    print_to_stdout!(
+                        "  - {} (skipped: runtime {} not configured by the crate)",
+                        display_name,
+                        runtime
+                    );
+                    continue;
+                }
+                (Some(runtime), _) => std::iter::once(format!("--{runtime}"))
+                    .chain(
+                        options
+                            .wasm_pack_test_options
+                            .iter()
+                            .filter(|option| config::runtime_of_option(option).is_none())
+                            .cloned(),
+                    )
+                    .collect(),
+                (None, Some(environment)) => config::auto_runtime_options(
+                    &default_runtimes
+                        .iter()
+                        .map(|runtime| format!("--{runtime}"))
+                        .chain(options.wasm_pack_test_options.iter().cloned())
+                        .collect::<Vec<_>>(),
+                    &crate_config.wasm_pack_args,
+                    environment,
+                ),
+                (None, None) => options.wasm_pack_test_options.clone(),
+            };
+            match crate_config.invocation(
+                &crate_path,
+                &wasm_pack_test_options,
+                &options.cargo_test_options,
+            ) {
+                Ok(mut invocation) => {
+                    invocation.runtime = runtime.map(str::to_string);
+                    invocations.push(invocation);
+                }
+                Err(reason) => {
+                    print_to_stdout!("  - {} (skipped: {})", display_name, reason)
+                }
+            }
+        }
+    }
+    Ok(invocations)
 }
 
 #[doc(hidden)]
@@ -142,14 +1007,17 @@ fn parse_options(args: &[String]) -> Result<(Option<String>, Vec<String>, Vec<St
 fn run(args: Vec<String>) -> ExitCode {
     let mut exitcode = ExitCode::Success;
 
-    let (path_argument, wasm_pack_test_options, cargo_test_options) = match parse_options(&args) {
+    let mut options = match parse_options(&args) {
         Ok(options) => options,
         Err(exitcode) => {
             return exitcode;
         }
     };
+    if options.message_format_json {
+        events::enable();
+    }
 
-    let path = if let Some(path) = path_argument {
+    let path = if let Some(path) = &options.path_argument {
         let pathbuf = std::path::PathBuf::from(path);
         if !pathbuf.exists() {
             // If the path does not exist, print an error message and exit with code 1
@@ -166,59 +1034,137 @@ fn run(args: Vec<String>) -> ExitCode {
         std::env::current_dir().unwrap()
     };
 
-    #[cfg(feature = "workspace")]
-    let crates_paths = {
-        let cargo_toml_path = path.join("Cargo.toml");
-        if cargo_toml_path.is_file() {
-            let content = std::fs::read_to_string(cargo_toml_path).unwrap_or_default();
-            if content.contains("[workspace]") {
-                let content_parsed = toml::de::from_str::<toml::Value>(&content)
-                    .unwrap_or(toml::Value::Table(toml::map::Map::new()));
-                let workspace_members = content_parsed
-                    .get("workspace")
-                    .and_then(|v| v.get("members"))
-                    .and_then(|v| v.as_array())
-                    .unwrap_or(&Vec::new())
-                    .iter()
-                    .filter_map(|v| v.as_str())
-                    .map(|s| path.join(s))
-                    .collect::<Vec<_>>();
-                if workspace_members.is_empty() {
-                    print_to_stderr!("No crates found in the workspace {}.", path.display());
-                    return ExitCode::NoCratesFound;
-                }
-                for workspace_member in &workspace_members {
-                    if !workspace_member.exists() {
-                        print_to_stderr!(
-                            "The workspace member {} does not exists.",
-                            workspace_member.display()
-                        );
-                        return ExitCode::PathNotFound;
-                    }
-                    if !workspace_member.is_dir() {
-                        print_to_stderr!(
-                            "The workspace member {} is not a directory.",
-                            workspace_member.display()
-                        );
-                        return ExitCode::NotADirectory;
-                    }
+    let config_file = config::find_config_file(&path);
+    match config_file {
+        Some(config_file) => {
+            match config::Settings::from_file(&config_file, options.profile.as_deref()) {
+                Ok(settings) => apply_file_settings(&mut options, config_file, settings),
+                Err(error) => {
+                    print_to_stderr!(
+                        "Invalid configuration file {}: {}.",
+                        config_file.display(),
+                        error
+                    );
+                    return ExitCode::InvalidOption;
                 }
-                print_to_stdout!(
-                    "Found {} crates in the workspace {}",
-                    workspace_members.len(),
-                    path.display()
-                );
-                workspace_members
-            } else {
-                gather_crate_paths!(path)
             }
+        }
+        None if options.profile.is_some() => {
+            print_to_stderr!(
+                "The option --profile requires a {} file in {} or its parent directories.",
+                config::CONFIG_FILE,
+                path.display()
+            );
+            return ExitCode::InvalidOption;
+        }
+        None => {}
+    }
+
+    #[cfg(feature = "workspace")]
+    let path_absolute =
+        discovery::normalize_path(&std::path::absolute(&path).unwrap_or_else(|_| path.clone()));
+    #[cfg(feature = "workspace")]
+    let workspace_root = discovery::find_workspace_root(&path).map(|workspace_root| {
+        if workspace_root == path_absolute {
+            path.clone()
         } else {
-            gather_crate_paths!(path)
+            workspace_root
+        }
+    });
+    #[cfg(feature = "workspace")]
+    if let Some(workspace_root) = &workspace_root {
+        match config::WorkspaceConfig::from_workspace(workspace_root) {
+            Ok(defaults) => apply_workspace_defaults(&mut options, workspace_root, defaults),
+            Err(error) => {
+                print_to_stderr!(
+                    "Invalid [workspace.metadata.wasm-pack-test-all] table in {}: {}.",
+                    workspace_root.join("Cargo.toml").display(),
+                    error
+                );
+                return ExitCode::InvalidOption;
+            }
+        }
+    }
+    // `--workspace` tests all the members of the workspace enclosing the path
+    #[cfg(feature = "workspace")]
+    let path = match &workspace_root {
+        Some(workspace_root) if options.workspace => workspace_root.clone(),
+        _ => path,
+    };
+    #[cfg(not(feature = "workspace"))]
+    if options.workspace && !options.cargo_metadata_discovery {
+        print_to_stderr!("The option --workspace requires the `workspace` feature.");
+        return ExitCode::InvalidOption;
+    }
+
+    if options.print_config {
+        print_config(&options, &path);
+        return ExitCode::Success;
+    }
+
+    #[cfg(feature = "workspace")]
+    let crates_paths = if options.cargo_metadata_discovery {
+        match cargo_metadata_crates_paths(&path, &options) {
+            Ok(crates_paths) => crates_paths,
+            Err(exitcode) => return exitcode,
+        }
+    } else if let Some(workspace_root) = workspace_root {
+        let manifest = discovery::read_manifest(&workspace_root)
+            .unwrap_or(toml::Value::Table(toml::map::Map::new()));
+        let workspace_members = match discovery::expand_workspace_members(
+            &workspace_root,
+            &discovery::workspace_strings(&manifest, "members"),
+            &discovery::workspace_strings(&manifest, "exclude"),
+        ) {
+            Ok(workspace_members) => workspace_members,
+            Err(pattern) => {
+                print_to_stderr!(
+                    "The workspace member pattern {} does not match any crate in the workspace {}.",
+                    pattern,
+                    workspace_root.display()
+                );
+                return ExitCode::PathNotFound;
+            }
+        };
+        let workspace_members =
+            members_at_path(&path, &workspace_root, workspace_members, &options);
+        if workspace_members.is_empty() {
+            print_to_stderr!(
+                "No crates found in the workspace {}.",
+                workspace_root.display()
+            );
+            return ExitCode::NoCratesFound;
         }
+        for workspace_member in &workspace_members {
+            if !workspace_member.exists() {
+                print_to_stderr!(
+                    "The workspace member {} does not exists.",
+                    workspace_member.display()
+                );
+                return ExitCode::PathNotFound;
+            }
+            if !workspace_member.is_dir() {
+                print_to_stderr!(
+                    "The workspace member {} is not a directory.",
+                    workspace_member.display()
+                );
+                return ExitCode::NotADirectory;
+            }
+        }
+        testable_members(&workspace_root, workspace_members)
+    } else {
+        gather_crate_paths!(path, options)
     };
 
     #[cfg(not(feature = "workspace"))]
-    let crates_paths = gather_crate_paths!(path);
+    let crates_paths = if options.cargo_metadata_discovery {
+        match cargo_metadata_crates_paths(&path, &options) {
+            Ok(crates_paths) => crates_paths,
+            Err(exitcode) => return exitcode,
+        }
+    } else {
+        gather_crate_paths!(path, options)
+    };
 
     if crates_paths.is_empty() {
         print_to_stderr!(
@@ -230,69 +1176,141 @@ fn run(args: Vec<String>) -> ExitCode {
         return ExitCode::NoTestsFound;
     }
 
+    let crates_paths = if options.packages.is_empty() && options.excluded_packages.is_empty() {
+        crates_paths
+    } else {
+        match select_packages(crates_paths, &options) {
+            Ok(crates_paths) => crates_paths,
+            Err(exitcode) => return exitcode,
+        }
+    };
+
+    let crates_paths = match &options.changed_since {
+        Some(reference) => match changed_crates_paths(&path, crates_paths, reference) {
+            Ok(crates_paths) if crates_paths.is_empty() => {
+                print_to_stdout!("No crates changed since {}.", reference);
+                return ExitCode::Success;
+            }
+            Ok(crates_paths) => crates_paths,
+            Err(exitcode) => return exitcode,
+        },
+        None => crates_paths,
+    };
+
+    let crates_paths = if options.files_mode {
+        let crates_paths = files_crates_paths(crates_paths, &options);
+        if crates_paths.is_empty() {
+            print_to_stdout!("No crates own the passed files.");
+            return ExitCode::Success;
+        }
+        crates_paths
+    } else {
+        crates_paths
+    };
+
+    let invocations = match crate_invocations(&path, crates_paths, &options) {
+        Ok(invocations) if invocations.is_empty() => {
+            print_to_stdout!("All the crates were skipped.");
+            return ExitCode::Success;
+        }
+        Ok(invocations) => invocations,
+        Err(exitcode) => return exitcode,
+    };
+
+    events::emit(
+        "discovery-finished",
+        vec![
+            ("path", path.display().to_string().into()),
+            (
+                "crates",
+                json::Json::Array(
+                    invocations
+                        .iter()
+                        .map(|invocation| invocation.crate_path.display().to_string().into())
+                        .collect(),
+                ),
+            ),
+        ],
+    );
+
+    if options.dry_run {
+        print_plan(&invocations);
+        return ExitCode::Success;
+    }
+
     print_to_stdout!("Running tests for the next crates:");
-    for crate_path in &crates_paths {
-        print_to_stdout!("  - {}", crate_path.display());
+    for invocation in &invocations {
+        print_to_stdout!("  - {}", invocation.display_name(std::path::Path::new("")));
     }
 
-    for crate_path in crates_paths {
-        let args = format!(
-            "wasm-pack test {}{}{}",
-            if wasm_pack_test_options.is_empty() {
-                String::new()
-            } else {
-                format!(
-                    "{} ",
-                    wasm_pack_test_options
-                        .iter()
-                        .map(|s| s.to_string())
-                        .collect::<Vec<_>>()
-                        .join(" ")
-                )
-            },
-            crate_path.display(),
-            if cargo_test_options.is_empty() {
-                String::new()
-            } else {
-                format!(
-                    " {}",
-                    cargo_test_options
-                        .iter()
-                        .map(|s| s.to_string())
-                        .collect::<Vec<_>>()
-                        .join(" ")
-                )
-            }
-        );
-        print_to_stdout!("+ {}", args);
-        let status = std::process::Command::new("wasm-pack")
-            .arg("test")
-            .args(&wasm_pack_test_options)
-            .arg(&crate_path)
-            .args(&cargo_test_options)
-            .stdout(std::process::Stdio::inherit())
-            .stderr(std::process::Stdio::inherit())
-            .status()
-            .unwrap_or_else(|_| {
-                if std::io::Error::last_os_error().kind() == std::io::ErrorKind::NotFound {
-                    print_to_stderr!(
-                        "Binary wasm-pack not found. Make sure it is installed and in your PATH."
-                    );
-                } else {
-                    print_to_stderr!(
-                        "`wasm-pack test` command failed with error: {}",
-                        std::io::Error::last_os_error()
-                    );
-                }
-                std::process::exit(ExitCode::ExternalError as u8 as i32);
-            });
-        if !status.success() {
-            exitcode = ExitCode::TestsFailed;
+    let start = std::time::Instant::now();
+    let crate_runs = runner::run_crates(
+        &path,
+        invocations,
+        &runner::RunSettings {
+            jobs: options.jobs.unwrap_or(1),
+            fail_fast: options.fail_fast,
+            timeout: options.timeout,
+            total_timeout: options.total_timeout,
+            retries: options.retries.unwrap_or(0),
+        },
+    );
+    if crate_runs
+        .iter()
+        .any(|crate_run| crate_run.status == runner::CrateStatus::TimedOut)
+    {
+        exitcode = ExitCode::TimedOut;
+    } else if crate_runs.iter().any(|crate_run| {
+        crate_run.status == runner::CrateStatus::Failed
+            || (options.fail_on_flaky && crate_run.status == runner::CrateStatus::Flaky)
+    }) {
+        exitcode = ExitCode::TestsFailed;
+    }
+
+    let duration = start.elapsed();
+    summary::print_summary(&path, &crate_runs, duration);
+
+    if let Some(junit_path) = &options.junit {
+        let report = junit::build_report(&path, &crate_runs, duration);
+        if let Err(error) = std::fs::write(junit_path, report) {
+            print_to_stderr!(
+                "Failed to write the JUnit report to {}: {}",
+                junit_path.display(),
+                error
+            );
+            return ExitCode::ExternalError;
         }
+        print_to_stdout!("JUnit report written to {}.", junit_path.display());
     }
 
+    let mut total_counts = output::TestCounts::default();
+    for crate_run in &crate_runs {
+        total_counts += crate_run.test_counts.unwrap_or_default();
+    }
+    events::emit(
+        "run-finished",
+        vec![
+            (
+                "status",
+                if exitcode == ExitCode::Success {
+                    "passed"
+                } else {
+                    "failed"
+                }
+                .into(),
+            ),
+            ("exit_code", json::Json::Int(exitcode as u8 as i64)),
+            ("crates", crate_runs.len().into()),
+            ("passed", total_counts.passed.into()),
+            ("failed", total_counts.failed.into()),
+            ("ignored", total_counts.ignored.into()),
+        ],
+    );
+
     if exitcode == ExitCode::Success {
         print_to_stdout!("All tests passed!");
+    } else if exitcode == ExitCode::TimedOut {
+        print_to_stderr!("Some crates timed out.");
     } else {
         print_to_stderr!("Some tests failed.");
     }
@@ -300,50 +1318,36 @@ fn run(args: Vec<String>) -> ExitCode {
     exitcode
 }
 
-fn gather_crates_paths_in_dir_or_subdirs(path: &std::path::PathBuf) -> Vec<std::path::PathBuf> {
-    let mut paths = Vec::new();
-    if let Ok(entries) = std::fs::read_dir(path) {
-        for maybe_entry in entries {
-            if let Ok(entry) = maybe_entry {
-                let entry_path = entry.path();
-
-                if entry_path.is_dir() {
-                    paths.extend(gather_crates_paths_in_dir_or_subdirs(&entry_path));
-                } else if entry_path.file_name() == Some(std::ffi::OsStr::new("Cargo.toml")) {
-                    if let Some(parent) = entry_path.parent() {
-                        let new_path = parent.to_path_buf();
-                        if is_testable_crate(&new_path) {
-                            paths.push(new_path.clone());
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    paths
-}
-
-fn is_testable_crate(crate_path: &std::path::PathBuf) -> bool {
-    let mut found = false;
-    if let Ok(entries) = std::fs::read_dir(crate_path) {
-        for maybe_entry in entries {
-            if let Ok(entry) = maybe_entry {
-                if entry.path().is_dir() {
-                    found = is_testable_crate(&entry.path());
-                    if found {
-                        break;
-                    }
-                } else if entry.path().is_file() {
-                    let content = std::fs::read_to_string(entry.path()).unwrap_or_default();
-                    if content.contains("#[wasm_bindgen_test]") {
-                        found = true;
-                        break;
-                    }
-                }
-            }
-        }
+/// Print the crates that would be tested and the commands to test them.
+fn print_plan(invocations: &[runner::CrateInvocation]) {
+    print_to_stdout!("The next commands would be executed:");
+    for invocation in invocations {
+        let crate_path = &invocation.crate_path;
+        let command = runner::format_command(
+            crate_path,
+            &invocation.envs,
+            &invocation.wasm_pack_test_options,
+            &invocation.cargo_test_options,
+        );
+        print_to_stdout!("  - {}", invocation.display_name(std::path::Path::new("")));
+        print_to_stdout!("    + {}", command);
+        let mut fields = runner::crate_event_fields(crate_path, invocation.runtime.as_deref());
+        fields.extend([
+            ("command", command.into()),
+            (
+                "args",
+                json::Json::Array(
+                    std::iter::once("wasm-pack".to_string())
+                        .chain(runner::command_args(
+                            crate_path,
+                            &invocation.wasm_pack_test_options,
+                            &invocation.cargo_test_options,
+                        ))
+                        .map(Into::into)
+                        .collect(),
+                ),
+            ),
+        ]);
+        events::emit("crate-planned", fields);
     }
-
-    found
 }
@@ -0,0 +1,160 @@
+//! Parsing of the output printed by `wasm-bindgen-test-runner`, which
+//! follows the format of the libtest harness.
+
+/// Outcome of a single test reported by `wasm-bindgen-test-runner`.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum TestOutcome {
+    Passed,
+    Failed,
+    Ignored,
+}
+
+/// Test function found in the output of a crate.
+pub(crate) struct TestCase {
+    pub(crate) name: String,
+    pub(crate) outcome: TestOutcome,
+    /// Output printed by the test when it failed.
+    pub(crate) failure_output: String,
+}
+
+/// Number of tests by outcome reported for a crate.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct TestCounts {
+    pub(crate) passed: usize,
+    pub(crate) failed: usize,
+    pub(crate) ignored: usize,
+}
+
+impl std::ops::AddAssign for TestCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.passed += other.passed;
+        self.failed += other.failed;
+        self.ignored += other.ignored;
+    }
+}
+
+/// Remove ANSI escape sequences, like colors, from a string.
+pub(crate) fn strip_ansi_codes(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            result.push(c);
+            continue;
+        }
+        if chars.peek() == Some(&'[') {
+            chars.next();
+            // CSI sequences end with a character in the range `@` to `~`
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        } else {
+            chars.next();
+        }
+    }
+    result
+}
+
+/// Parse lines like `test name ... ok` into the name and outcome of a test.
+fn parse_test_line(line: &str) -> Option<(&str, TestOutcome)> {
+    let (name, result) = line.strip_prefix("test ")?.split_once(" ... ")?;
+    let outcome = if result.starts_with("ok") {
+        TestOutcome::Passed
+    } else if result.starts_with("FAILED") {
+        TestOutcome::Failed
+    } else if result.starts_with("ignored") {
+        TestOutcome::Ignored
+    } else {
+        return None;
+    };
+    Some((name.trim(), outcome))
+}
+
+/// Parse headers of failure sections like `---- name output ----`.
+fn parse_failure_header(line: &str) -> Option<&str> {
+    let inner = line.strip_prefix("---- ")?.strip_suffix(" ----")?;
+    Some(
+        inner
+            .rsplit_once(' ')
+            .map(|(name, _)| name)
+            .unwrap_or(inner),
+    )
+}
+
+/// Parse the tests reported in the output of `wasm-pack test`.
+pub(crate) fn parse_test_cases(output: &str) -> Vec<TestCase> {
+    let mut test_cases: Vec<TestCase> = Vec::new();
+    let mut failure_name: Option<String> = None;
+
+    for line in strip_ansi_codes(output).lines() {
+        let trimmed = line.trim();
+        if let Some((name, outcome)) = parse_test_line(trimmed) {
+            failure_name = None;
+            test_cases.push(TestCase {
+                name: name.to_string(),
+                outcome,
+                failure_output: String::new(),
+            });
+        } else if let Some(name) = parse_failure_header(trimmed) {
+            failure_name = Some(name.to_string());
+        } else if trimmed == "failures:" || trimmed.starts_with("test result:") {
+            failure_name = None;
+        } else if let Some(name) = &failure_name {
+            if let Some(test_case) = test_cases.iter_mut().rev().find(|t| &t.name == name) {
+                test_case.failure_output.push_str(line);
+                test_case.failure_output.push('\n');
+            }
+        }
+    }
+
+    test_cases
+}
+
+/// Parse lines like `test result: ok. 1 passed; 0 failed; 0 ignored; ...`.
+fn parse_test_result_line(line: &str) -> Option<TestCounts> {
+    let (_, results) = line.strip_prefix("test result:")?.split_once(". ")?;
+    let mut counts = TestCounts::default();
+    for result in results.split(';') {
+        let Some((number, label)) = result.trim().split_once(' ') else {
+            continue;
+        };
+        let Ok(number) = number.parse::<usize>() else {
+            continue;
+        };
+        match label {
+            "passed" => counts.passed = number,
+            "failed" => counts.failed = number,
+            "ignored" => counts.ignored = number,
+            _ => {}
+        }
+    }
+    Some(counts)
+}
+
+/// Count the tests reported in the output of `wasm-pack test`.
+///
+/// The counts of all `test result:` lines are summed, as a crate can have
+/// multiple test targets. If there is none, like when the runner is
+/// interrupted, the tests found in `test_cases` are counted instead.
+pub(crate) fn parse_test_counts(output: &str, test_cases: &[TestCase]) -> Option<TestCounts> {
+    let mut counts: Option<TestCounts> = None;
+    for line in strip_ansi_codes(output).lines() {
+        if let Some(line_counts) = parse_test_result_line(line.trim()) {
+            *counts.get_or_insert_with(TestCounts::default) += line_counts;
+        }
+    }
+    if counts.is_none() && !test_cases.is_empty() {
+        let mut test_cases_counts = TestCounts::default();
+        for test_case in test_cases {
+            match test_case.outcome {
+                TestOutcome::Passed => test_cases_counts.passed += 1,
+                TestOutcome::Failed => test_cases_counts.failed += 1,
+                TestOutcome::Ignored => test_cases_counts.ignored += 1,
+            }
+        }
+        counts = Some(test_cases_counts);
+    }
+    counts
+}
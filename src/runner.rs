@@ -0,0 +1,615 @@
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::events;
+use crate::json::Json;
+use crate::output::{self, TestCase, TestCounts};
+use crate::summary::format_duration;
+use crate::ExitCode;
+
+/// Final status of a crate after running its tests.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum CrateStatus {
+    Passed,
+    Failed,
+    /// Killed while running because the run was stopped.
+    Cancelled,
+    /// Killed while running because it exceeded a timeout.
+    TimedOut,
+    /// Not run because the run was stopped before reaching it.
+    Skipped,
+    /// Failed but passed when retried.
+    Flaky,
+}
+
+impl CrateStatus {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            CrateStatus::Passed => "passed",
+            CrateStatus::Failed => "failed",
+            CrateStatus::Cancelled => "cancelled",
+            CrateStatus::TimedOut => "timeout",
+            CrateStatus::Skipped => "skipped",
+            CrateStatus::Flaky => "flaky",
+        }
+    }
+
+    /// Whether the crate did not pass by its own, being retried if allowed.
+    pub(crate) fn is_failure(&self) -> bool {
+        matches!(self, CrateStatus::Failed | CrateStatus::TimedOut)
+    }
+}
+
+/// Result of running `wasm-pack test` for a crate.
+pub(crate) struct CrateRun {
+    pub(crate) crate_path: PathBuf,
+    /// Runtime of the run when testing a matrix of runtimes.
+    pub(crate) runtime: Option<String>,
+    pub(crate) status: CrateStatus,
+    /// Exit code of `wasm-pack test`, if the process exited normally.
+    pub(crate) exit_code: Option<i32>,
+    pub(crate) duration: Duration,
+    /// Output captured from `wasm-pack test`.
+    pub(crate) stdout: String,
+    pub(crate) stderr: String,
+    /// Tests found parsing the output of `wasm-pack test`.
+    pub(crate) test_cases: Vec<TestCase>,
+    /// Number of tests by outcome, if they could be parsed from the output.
+    pub(crate) test_counts: Option<TestCounts>,
+    /// Number of times that `wasm-pack test` was executed for the crate.
+    pub(crate) attempts: usize,
+}
+
+impl CrateRun {
+    /// Result for a crate whose `wasm-pack test` process was not executed.
+    fn not_run(invocation: &CrateInvocation, status: CrateStatus, stderr: String) -> Self {
+        Self {
+            crate_path: invocation.crate_path.clone(),
+            runtime: invocation.runtime.clone(),
+            status,
+            exit_code: None,
+            duration: Duration::ZERO,
+            stdout: String::new(),
+            stderr,
+            test_cases: Vec::new(),
+            test_counts: None,
+            attempts: 0,
+        }
+    }
+
+    /// Path of the crate relative to `root`, followed by the runtime if any.
+    pub(crate) fn display_name(&self, root: &Path) -> String {
+        display_crate_run(root, &self.crate_path, self.runtime.as_deref())
+    }
+}
+
+/// Path of a crate relative to the directory where crates are discovered.
+pub(crate) fn display_crate_path(root: &Path, crate_path: &Path) -> String {
+    crate_path
+        .strip_prefix(root)
+        .unwrap_or(crate_path)
+        .display()
+        .to_string()
+}
+
+/// Path of a crate relative to `root`, followed by the runtime of the run,
+/// like `crates/app (chrome)`, when testing a matrix of runtimes.
+pub(crate) fn display_crate_run(root: &Path, crate_path: &Path, runtime: Option<&str>) -> String {
+    match runtime {
+        Some(runtime) => format!("{} ({runtime})", display_crate_path(root, crate_path)),
+        None => display_crate_path(root, crate_path),
+    }
+}
+
+/// Fields that identify the run of a crate in the events.
+pub(crate) fn crate_event_fields(
+    crate_path: &Path,
+    runtime: Option<&str>,
+) -> Vec<(&'static str, Json)> {
+    let mut fields = vec![("crate", crate_path.display().to_string().into())];
+    if let Some(runtime) = runtime {
+        fields.push(("runtime", runtime.into()));
+    }
+    fields
+}
+
+/// Environment variable read by `wasm-bindgen-test-runner` to know where
+/// to bind the server that serves the tests to browsers.
+const TEST_ADDRESS_ENV: &str = "WASM_BINDGEN_TEST_ADDRESS";
+
+/// Ports handed to the crates being tested concurrently.
+///
+/// Each port is obtained asking the OS for a free one and is kept reserved
+/// until the crate that uses it finishes, so two running crates never share
+/// the same port even if the OS returns it twice.
+#[derive(Default)]
+struct PortAllocator {
+    in_use: Mutex<HashSet<u16>>,
+}
+
+impl PortAllocator {
+    const MAX_ATTEMPTS: usize = 64;
+
+    fn reserve(&self) -> Result<u16, String> {
+        for _ in 0..Self::MAX_ATTEMPTS {
+            let port = std::net::TcpListener::bind("127.0.0.1:0")
+                .and_then(|listener| listener.local_addr())
+                .map_err(|error| error.to_string())?
+                .port();
+            if self.in_use.lock().unwrap().insert(port) {
+                return Ok(port);
+            }
+        }
+        Err(format!(
+            "all {} ports offered by the OS are already in use by other crates",
+            Self::MAX_ATTEMPTS
+        ))
+    }
+
+    fn release(&self, port: u16) {
+        self.in_use.lock().unwrap().remove(&port);
+    }
+}
+
+/// Arguments passed to `wasm-pack` to test a crate.
+pub(crate) fn command_args(
+    crate_path: &Path,
+    wasm_pack_test_options: &[String],
+    cargo_test_options: &[String],
+) -> Vec<String> {
+    let mut args = vec!["test".to_string()];
+    args.extend(wasm_pack_test_options.iter().cloned());
+    args.push(crate_path.display().to_string());
+    args.extend(cargo_test_options.iter().cloned());
+    args
+}
+
+/// Quote an argument for a POSIX shell if needed.
+fn shell_quote(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_=+./:,@%".contains(c))
+    {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Build the command line that will be executed for a crate, for display.
+pub(crate) fn format_command(
+    crate_path: &Path,
+    envs: &[(String, String)],
+    wasm_pack_test_options: &[String],
+    cargo_test_options: &[String],
+) -> String {
+    let mut args = envs
+        .iter()
+        .map(|(key, value)| format!("{key}={}", shell_quote(value)))
+        .collect::<Vec<_>>();
+    args.push("wasm-pack".to_string());
+    args.extend(
+        command_args(crate_path, wasm_pack_test_options, cargo_test_options)
+            .iter()
+            .map(|arg| shell_quote(arg)),
+    );
+    args.join(" ")
+}
+
+/// How to run `wasm-pack test` for a crate.
+pub(crate) struct CrateInvocation {
+    pub(crate) crate_path: PathBuf,
+    pub(crate) wasm_pack_test_options: Vec<String>,
+    pub(crate) cargo_test_options: Vec<String>,
+    /// Environment variables set for `wasm-pack test`.
+    pub(crate) envs: Vec<(String, String)>,
+    /// Runtime of the run when testing a matrix of runtimes.
+    pub(crate) runtime: Option<String>,
+}
+
+impl CrateInvocation {
+    /// Path of the crate relative to `root`, followed by the runtime if any.
+    pub(crate) fn display_name(&self, root: &Path) -> String {
+        display_crate_run(root, &self.crate_path, self.runtime.as_deref())
+    }
+}
+
+/// Settings shared by all the crates of a run.
+pub(crate) struct RunSettings {
+    pub(crate) jobs: usize,
+    /// Stop testing crates after the first one that fails.
+    pub(crate) fail_fast: bool,
+    /// Maximum time that testing each crate can take.
+    pub(crate) timeout: Option<Duration>,
+    /// Maximum time that testing all crates can take.
+    pub(crate) total_timeout: Option<Duration>,
+    /// Number of times that a failed crate is tested again.
+    pub(crate) retries: usize,
+}
+
+/// State shared by the workers testing crates.
+struct Pool<'a> {
+    root: &'a Path,
+    settings: &'a RunSettings,
+    jobs: usize,
+    ports: PortAllocator,
+    /// Set when the run is stopped, so running crates are killed and the
+    /// remaining ones are skipped.
+    cancelled: AtomicBool,
+    /// Instant at which the run must be stopped, from `--total-timeout`.
+    deadline: Option<Instant>,
+}
+
+/// Run `wasm-pack test` for all crates using a pool of `jobs` workers.
+///
+/// Results are returned in the same order as `invocations`.
+pub(crate) fn run_crates(
+    root: &Path,
+    invocations: Vec<CrateInvocation>,
+    settings: &RunSettings,
+) -> Vec<CrateRun> {
+    let n_crates = invocations.len();
+    let pool = Pool {
+        root,
+        settings,
+        jobs: settings.jobs.clamp(1, n_crates.max(1)),
+        ports: PortAllocator::default(),
+        cancelled: AtomicBool::new(false),
+        deadline: settings
+            .total_timeout
+            .map(|total_timeout| Instant::now() + total_timeout),
+    };
+    let next_crate_index = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<CrateRun>>> = Mutex::new((0..n_crates).map(|_| None).collect());
+
+    std::thread::scope(|scope| {
+        for _ in 0..pool.jobs {
+            scope.spawn(|| loop {
+                let index = next_crate_index.fetch_add(1, Ordering::SeqCst);
+                if index >= n_crates {
+                    break;
+                }
+                let invocation = &invocations[index];
+                let crate_run = if pool.stopped() {
+                    CrateRun::not_run(invocation, CrateStatus::Skipped, String::new())
+                } else {
+                    pool.run_crate_with_retries(invocation)
+                };
+                if settings.fail_fast && crate_run.status.is_failure() {
+                    pool.cancelled.store(true, Ordering::SeqCst);
+                }
+                emit_crate_finished(&crate_run);
+                results.lock().unwrap()[index] = Some(crate_run);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Time between checks of the state of a running `wasm-pack test` process.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+impl Pool<'_> {
+    /// Whether no more crates must be started.
+    fn stopped(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Test a crate, testing it again while it fails up to `retries` times.
+    ///
+    /// The result of the last attempt is returned, marked as flaky if the
+    /// crate passed after failing.
+    fn run_crate_with_retries(&self, invocation: &CrateInvocation) -> CrateRun {
+        let mut crate_run = self.run_crate(invocation);
+        let mut attempts = 1;
+        let mut previous_duration = Duration::ZERO;
+        while crate_run.status.is_failure() && attempts <= self.settings.retries && !self.stopped()
+        {
+            print_to_stderr!(
+                "Retrying the crate {} ({}/{}).",
+                invocation.display_name(Path::new("")),
+                attempts,
+                self.settings.retries
+            );
+            previous_duration += crate_run.duration;
+            attempts += 1;
+            crate_run = self.run_crate(invocation);
+            if crate_run.status == CrateStatus::Passed {
+                crate_run.status = CrateStatus::Flaky;
+            }
+        }
+        crate_run.attempts = attempts;
+        crate_run.duration += previous_duration;
+        crate_run
+    }
+
+    fn run_crate(&self, invocation: &CrateInvocation) -> CrateRun {
+        // Only prefix the output of each crate when it can interleave
+        // with the output of others.
+        let label = if self.jobs > 1 {
+            Some(invocation.display_name(self.root))
+        } else {
+            None
+        };
+        // Concurrent browser runs would collide binding the default
+        // address of the test server, so give each one its own port.
+        let port = if self.jobs > 1 {
+            match self.ports.reserve() {
+                Ok(port) => Some(port),
+                Err(error) => {
+                    print_to_stderr!(
+                        "No free port available for the test server of the crate {}: {}.",
+                        invocation.display_name(Path::new("")),
+                        error
+                    );
+                    return CrateRun::not_run(invocation, CrateStatus::Failed, error);
+                }
+            }
+        } else {
+            None
+        };
+        let mut envs = invocation.envs.clone();
+        if let Some(port) = port {
+            envs.push((TEST_ADDRESS_ENV.to_string(), format!("127.0.0.1:{port}")));
+        }
+        let crate_run = self.run_wasm_pack_test(invocation, &envs, label.as_deref());
+        if let Some(port) = port {
+            self.ports.release(port);
+        }
+        crate_run
+    }
+
+    fn run_wasm_pack_test(
+        &self,
+        invocation: &CrateInvocation,
+        envs: &[(String, String)],
+        label: Option<&str>,
+    ) -> CrateRun {
+        let settings = self.settings;
+        let crate_path = invocation.crate_path.as_path();
+        let command = format_command(
+            crate_path,
+            envs,
+            &invocation.wasm_pack_test_options,
+            &invocation.cargo_test_options,
+        );
+        print_to_stdout!("+ {}", command);
+        let mut fields = crate_event_fields(crate_path, invocation.runtime.as_deref());
+        fields.push(("command", command.into()));
+        events::emit("crate-started", fields);
+
+        let start = Instant::now();
+        let deadline = [
+            settings.timeout.map(|timeout| start + timeout),
+            self.deadline,
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+        let mut command = std::process::Command::new("wasm-pack");
+        command
+            .args(command_args(
+                crate_path,
+                &invocation.wasm_pack_test_options,
+                &invocation.cargo_test_options,
+            ))
+            .envs(envs.iter().map(|(key, value)| (key, value)))
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+        let mut child = command.spawn().unwrap_or_else(|error| {
+            if error.kind() == std::io::ErrorKind::NotFound {
+                print_to_stderr!(
+                    "Binary wasm-pack not found. Make sure it is installed and in your PATH."
+                );
+            } else {
+                print_to_stderr!("`wasm-pack test` command failed with error: {}", error);
+            }
+            std::process::exit(ExitCode::ExternalError as u8 as i32);
+        });
+
+        // The output is forwarded as it comes and captured to be parsed later
+        let child_stdout = child.stdout.take().unwrap();
+        let child_stderr = child.stderr.take().unwrap();
+        let mut stopped_status = None;
+        let (stdout, stderr, exit_status) = std::thread::scope(|scope| {
+            let stdout = scope.spawn(|| {
+                tee_lines(child_stdout, label, || -> Box<dyn Write> {
+                    // stdout is reserved to events when they are enabled
+                    if events::enabled() {
+                        Box::new(std::io::stderr())
+                    } else {
+                        Box::new(std::io::stdout())
+                    }
+                })
+            });
+            let stderr = scope.spawn(|| tee_lines(child_stderr, label, std::io::stderr));
+
+            let exit_status = loop {
+                match child.try_wait() {
+                    Ok(Some(status)) => break Some(status),
+                    Ok(None) => {
+                        if stopped_status.is_none() {
+                            if self.cancelled.load(Ordering::SeqCst) {
+                                stopped_status = Some(CrateStatus::Cancelled);
+                            } else if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                                print_to_stderr!(
+                                    "Testing the crate {} timed out after {}.",
+                                    invocation.display_name(Path::new("")),
+                                    format_duration(start.elapsed())
+                                );
+                                stopped_status = Some(CrateStatus::TimedOut);
+                            }
+                            if stopped_status.is_some() {
+                                terminate_process_tree(&mut child);
+                            }
+                        }
+                        std::thread::sleep(POLL_INTERVAL);
+                    }
+                    Err(error) => {
+                        print_to_stderr!("`wasm-pack test` command failed with error: {}", error);
+                        break None;
+                    }
+                }
+            };
+            (stdout.join().unwrap(), stderr.join().unwrap(), exit_status)
+        });
+
+        let status = match exit_status {
+            _ if stopped_status.is_some() => stopped_status.unwrap(),
+            Some(exit_status) if exit_status.success() => CrateStatus::Passed,
+            _ => CrateStatus::Failed,
+        };
+        let test_cases = output::parse_test_cases(&stdout);
+        CrateRun {
+            crate_path: crate_path.to_path_buf(),
+            runtime: invocation.runtime.clone(),
+            status,
+            exit_code: exit_status.and_then(|exit_status| exit_status.code()),
+            duration: start.elapsed(),
+            test_counts: output::parse_test_counts(&stdout, &test_cases),
+            test_cases,
+            stdout,
+            stderr,
+            attempts: 1,
+        }
+    }
+}
+
+/// Kill a process and all its descendants, like the `cargo` and
+/// `wasm-bindgen-test-runner` processes spawned by `wasm-pack`, and the
+/// webdrivers and browsers spawned by them.
+///
+/// The processes stay in the process group of `wasm-pack-test-all`, so a
+/// Ctrl-C in the terminal reaches all of them.
+#[cfg(unix)]
+fn terminate_process_tree(child: &mut std::process::Child) {
+    fn signal(signal: &str, pids: &[u32]) {
+        let _ = std::process::Command::new("kill")
+            .arg(signal)
+            .args(pids.iter().map(|pid| pid.to_string()))
+            .stderr(std::process::Stdio::null())
+            .status();
+    }
+
+    // Processes are stopped as they are found, so they can't spawn more
+    // processes, and the tree is walked again until no new descendant shows
+    // up. They are killed at the end, because killed processes would
+    // reparent their children, breaking the tree.
+    let mut pids = vec![child.id()];
+    signal("-STOP", &pids);
+    loop {
+        let Ok(output) = std::process::Command::new("ps")
+            .args(["-A", "-o", "pid=", "-o", "ppid="])
+            .output()
+        else {
+            break;
+        };
+        let new_pids = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace().map(|f| f.parse::<u32>());
+                match (fields.next(), fields.next()) {
+                    (Some(Ok(pid)), Some(Ok(ppid))) => Some((pid, ppid)),
+                    _ => None,
+                }
+            })
+            .filter(|(pid, ppid)| pids.contains(ppid) && !pids.contains(pid))
+            .map(|(pid, _)| pid)
+            .collect::<Vec<_>>();
+        if new_pids.is_empty() {
+            break;
+        }
+        signal("-STOP", &new_pids);
+        pids.extend(new_pids);
+    }
+    signal("-KILL", &pids);
+    let _ = child.kill();
+}
+
+/// Kill a process and all its descendants, like the `cargo` and
+/// `wasm-bindgen-test-runner` processes spawned by `wasm-pack`, and the
+/// webdrivers and browsers spawned by them.
+#[cfg(windows)]
+fn terminate_process_tree(child: &mut std::process::Child) {
+    let _ = std::process::Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &child.id().to_string()])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status();
+    let _ = child.kill();
+}
+
+fn emit_crate_finished(crate_run: &CrateRun) {
+    let crate_path = &crate_run.crate_path;
+    let runtime = crate_run.runtime.as_deref();
+    let mut finished_fields = crate_event_fields(crate_path, runtime);
+    finished_fields.extend([
+        ("status", crate_run.status.as_str().into()),
+        (
+            "exit_code",
+            crate_run
+                .exit_code
+                .map(|code| Json::Int(code.into()))
+                .into(),
+        ),
+        ("duration", crate_run.duration.into()),
+        ("attempts", crate_run.attempts.into()),
+    ]);
+    events::emit("crate-finished", finished_fields);
+    if let Some(counts) = crate_run.test_counts {
+        let mut result_fields = crate_event_fields(crate_path, runtime);
+        result_fields.extend([
+            ("passed", counts.passed.into()),
+            ("failed", counts.failed.into()),
+            ("ignored", counts.ignored.into()),
+        ]);
+        events::emit("test-result", result_fields);
+    }
+}
+
+/// Write each line read from `reader` to `writer`, prefixed with `[label]`
+/// if a label is passed, and return all the content read.
+///
+/// Every line is written while holding the lock of the writer, so lines
+/// coming from different crates are never mixed.
+fn tee_lines<R, W, F>(reader: R, label: Option<&str>, writer: F) -> String
+where
+    R: std::io::Read,
+    W: Write,
+    F: Fn() -> W,
+{
+    let mut reader = std::io::BufReader::new(reader);
+    let mut captured = String::new();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                let content = String::from_utf8_lossy(&line);
+                let content = content.trim_end_matches(['\n', '\r']);
+                let _ = match label {
+                    Some(label) => writeln!(writer(), "[{}] {}", label, content),
+                    None => writeln!(writer(), "{}", content),
+                };
+                captured.push_str(content);
+                captured.push('\n');
+            }
+        }
+    }
+    captured
+}
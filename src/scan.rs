@@ -0,0 +1,354 @@
+//! Lightweight scanner of Rust source files used to find out which crates
+//! contain tests runnable by `wasm-pack test` and where they must run.
+//!
+//! Comments, string, character and byte literals are skipped, so only real
+//! code is taken into account.
+
+/// Token of Rust source code. Literals are discarded.
+#[derive(PartialEq)]
+enum Token {
+    Ident(String),
+    Punct(char),
+    Literal,
+}
+
+fn is_ident_start(c: char) -> bool {
+    c == '_' || c.is_alphabetic()
+}
+
+fn is_ident_continue(c: char) -> bool {
+    c == '_' || c.is_alphanumeric()
+}
+
+/// Skip the content of a string literal until its closing quote, honoring
+/// escapes. `chars` must be positioned after the opening quote.
+fn skip_string(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '"' => break,
+            _ => {}
+        }
+    }
+}
+
+/// Skip the content of a raw string literal with `hashes` number of `#`
+/// until its closing delimiter. `chars` must be positioned after the
+/// opening quote.
+fn skip_raw_string(chars: &mut std::iter::Peekable<std::str::Chars>, hashes: usize) {
+    while let Some(c) = chars.next() {
+        if c == '"' {
+            let mut closing_hashes = 0;
+            while closing_hashes < hashes && chars.peek() == Some(&'#') {
+                chars.next();
+                closing_hashes += 1;
+            }
+            if closing_hashes == hashes {
+                break;
+            }
+        }
+    }
+}
+
+/// Skip a block comment, which can be nested. `chars` must be positioned
+/// after the opening `/*`.
+fn skip_block_comment(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    let mut depth = 1;
+    while let Some(c) = chars.next() {
+        if c == '/' && chars.peek() == Some(&'*') {
+            chars.next();
+            depth += 1;
+        } else if c == '*' && chars.peek() == Some(&'/') {
+            chars.next();
+            depth -= 1;
+            if depth == 0 {
+                break;
+            }
+        }
+    }
+}
+
+/// Split Rust source code in tokens, discarding comments and literals.
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if c == '/' && chars.peek() == Some(&'/') {
+            for c in chars.by_ref() {
+                if c == '\n' {
+                    break;
+                }
+            }
+        } else if c == '/' && chars.peek() == Some(&'*') {
+            chars.next();
+            skip_block_comment(&mut chars);
+        } else if c == '"' {
+            skip_string(&mut chars);
+            tokens.push(Token::Literal);
+        } else if c == '\'' {
+            // Character literal or lifetime
+            match chars.next() {
+                Some('\\') => {
+                    // The escaped character, which can be `\` or `'`
+                    chars.next();
+                    while let Some(c) = chars.next() {
+                        if c == '\\' {
+                            chars.next();
+                        } else if c == '\'' {
+                            break;
+                        }
+                    }
+                    tokens.push(Token::Literal);
+                }
+                Some(_) if chars.peek() == Some(&'\'') => {
+                    chars.next();
+                    tokens.push(Token::Literal);
+                }
+                Some(c) if is_ident_continue(c) => {
+                    while chars.peek().is_some_and(|c| is_ident_continue(*c)) {
+                        chars.next();
+                    }
+                }
+                _ => {}
+            }
+        } else if c.is_ascii_digit() {
+            while chars
+                .peek()
+                .is_some_and(|c| is_ident_continue(*c) || *c == '.')
+            {
+                chars.next();
+            }
+            tokens.push(Token::Literal);
+        } else if is_ident_start(c) {
+            let mut ident = String::from(c);
+            while let Some(c) = chars.peek().copied().filter(|c| is_ident_continue(*c)) {
+                ident.push(c);
+                chars.next();
+            }
+            let is_string_prefix = matches!(ident.as_str(), "r" | "b" | "br" | "c" | "cr");
+            if is_string_prefix && chars.peek() == Some(&'"') {
+                chars.next();
+                if ident.contains('r') {
+                    skip_raw_string(&mut chars, 0);
+                } else {
+                    skip_string(&mut chars);
+                }
+                tokens.push(Token::Literal);
+            } else if is_string_prefix && ident.contains('r') && chars.peek() == Some(&'#') {
+                // Raw string like `r#"..."#` or raw identifier like `r#type`
+                let mut hashes = 0;
+                while chars.peek() == Some(&'#') {
+                    chars.next();
+                    hashes += 1;
+                }
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    skip_raw_string(&mut chars, hashes);
+                    tokens.push(Token::Literal);
+                } else {
+                    let mut raw_ident = String::new();
+                    while let Some(c) = chars.peek().copied().filter(|c| is_ident_continue(*c)) {
+                        raw_ident.push(c);
+                        chars.next();
+                    }
+                    tokens.push(Token::Ident(raw_ident));
+                }
+            } else if ident == "b" && chars.peek() == Some(&'\'') {
+                chars.next();
+                while let Some(c) = chars.next() {
+                    if c == '\\' {
+                        chars.next();
+                    } else if c == '\'' {
+                        break;
+                    }
+                }
+                tokens.push(Token::Literal);
+            } else {
+                tokens.push(Token::Ident(ident));
+            }
+        } else {
+            tokens.push(Token::Punct(c));
+        }
+    }
+
+    tokens
+}
+
+/// Get the tokens between a delimiter at `start` and its matching closing
+/// delimiter, returning them along with the index after the closing one.
+fn delimited(tokens: &[Token], start: usize) -> (&[Token], usize) {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(start) {
+        match token {
+            Token::Punct('[' | '(' | '{') => depth += 1,
+            Token::Punct(']' | ')' | '}') => {
+                depth -= 1;
+                if depth == 0 {
+                    return (&tokens[start + 1..i], i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    (&tokens[(start + 1).min(tokens.len())..], tokens.len())
+}
+
+/// Split tokens by commas that are not inside delimiters.
+fn split_top_level_commas(tokens: &[Token]) -> Vec<&[Token]> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut part_start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Punct('[' | '(' | '{') => depth += 1,
+            Token::Punct(']' | ')' | '}') => depth -= 1,
+            Token::Punct(',') if depth == 0 => {
+                parts.push(&tokens[part_start..i]);
+                part_start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&tokens[part_start..]);
+    parts
+}
+
+/// Parse a path like `a::b::c` at the start of the tokens, returning its
+/// segments and the number of tokens consumed.
+fn parse_path(tokens: &[Token]) -> (Vec<&str>, usize) {
+    let mut segments = Vec::new();
+    let mut i = 0;
+    if tokens.starts_with(&[Token::Punct(':'), Token::Punct(':')]) {
+        i = 2;
+    }
+    while let Some(Token::Ident(segment)) = tokens.get(i) {
+        segments.push(segment.as_str());
+        i += 1;
+        if tokens[i..].starts_with(&[Token::Punct(':'), Token::Punct(':')]) {
+            i += 2;
+        } else {
+            break;
+        }
+    }
+    (segments, i)
+}
+
+/// Names under which a macro is imported in a file, including the names
+/// given with `as` in `use` declarations.
+fn imported_names<'a>(tokens: &'a [Token], name: &'a str) -> Vec<&'a str> {
+    let mut names = vec![name];
+    for window in tokens.windows(3) {
+        if let [Token::Ident(imported), Token::Ident(as_), Token::Ident(alias)] = window {
+            if imported == name && as_ == "as" && alias != "_" {
+                names.push(alias);
+            }
+        }
+    }
+    names
+}
+
+/// Whether the content of an attribute, like `wasm_bindgen_test` in
+/// `#[wasm_bindgen_test]`, is one of `names` or applies one of them
+/// through `cfg_attr`.
+fn attribute_is_one_of(attribute: &[Token], names: &[&str], macro_name: &str) -> bool {
+    let (path, consumed) = parse_path(attribute);
+    if path == ["cfg_attr"] && attribute.get(consumed) == Some(&Token::Punct('(')) {
+        let (arguments, _) = delimited(attribute, consumed);
+        return split_top_level_commas(arguments)
+            .iter()
+            .skip(1)
+            .any(|attribute| attribute_is_one_of(attribute, names, macro_name));
+    }
+    match path.as_slice() {
+        [name] => names.contains(name),
+        [.., last] => *last == macro_name,
+        [] => false,
+    }
+}
+
+/// Whether a Rust source file contains an item with the attribute macro
+/// `macro_name`, used directly (`#[macro_name]`), with arguments
+/// (`#[macro_name(...)]`), with a path (`#[krate::macro_name]`), inside
+/// `cfg_attr` or imported with another name.
+fn has_attribute_macro(source: &str, macro_name: &str) -> bool {
+    let tokens = tokenize(source);
+    let names = imported_names(&tokens, macro_name);
+    let mut i = 0;
+    while i < tokens.len() {
+        if tokens[i] == Token::Punct('#') {
+            let mut start = i + 1;
+            if tokens.get(start) == Some(&Token::Punct('!')) {
+                start += 1;
+            }
+            if tokens.get(start) == Some(&Token::Punct('[')) {
+                let (attribute, end) = delimited(&tokens, start);
+                if attribute_is_one_of(attribute, &names, macro_name) {
+                    return true;
+                }
+                i = end;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    false
+}
+
+/// Whether a Rust source file contains a test marked with `#[wasm_bindgen_test]`.
+pub(crate) fn has_wasm_bindgen_test(source: &str) -> bool {
+    has_attribute_macro(source, "wasm_bindgen_test")
+}
+
+/// Environment where the tests of a crate run, configured with the
+/// `wasm_bindgen_test_configure!` macro.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum TestEnvironment {
+    /// `run_in_browser`
+    Browser,
+    /// `run_in_dedicated_worker` or `run_in_worker`
+    DedicatedWorker,
+    /// `run_in_shared_worker`
+    SharedWorker,
+    /// `run_in_service_worker`
+    ServiceWorker,
+}
+
+/// Get the environment configured by a `wasm_bindgen_test_configure!`
+/// invocation in a Rust source file, if any. Tests run in Node.js when the
+/// macro is not invoked.
+pub(crate) fn configured_test_environment(source: &str) -> Option<TestEnvironment> {
+    const MACRO_NAME: &str = "wasm_bindgen_test_configure";
+    let tokens = tokenize(source);
+    let names = imported_names(&tokens, MACRO_NAME);
+    for (i, window) in tokens.windows(3).enumerate() {
+        let [Token::Ident(name), Token::Punct('!'), Token::Punct('(' | '[' | '{')] = window else {
+            continue;
+        };
+        if !names.contains(&name.as_str()) {
+            continue;
+        }
+        let (arguments, _) = delimited(&tokens, i + 2);
+        let environment = arguments.iter().find_map(|token| match token {
+            Token::Ident(argument) => match argument.as_str() {
+                "run_in_browser" => Some(TestEnvironment::Browser),
+                "run_in_worker" | "run_in_dedicated_worker" => {
+                    Some(TestEnvironment::DedicatedWorker)
+                }
+                "run_in_shared_worker" => Some(TestEnvironment::SharedWorker),
+                "run_in_service_worker" => Some(TestEnvironment::ServiceWorker),
+                _ => None,
+            },
+            _ => None,
+        });
+        if environment.is_some() {
+            return environment;
+        }
+    }
+    None
+}
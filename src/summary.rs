@@ -0,0 +1,179 @@
+use std::path::Path;
+
+use crate::output::TestCounts;
+use crate::runner::{display_crate_path, CrateRun, CrateStatus};
+
+/// Format a duration as seconds with two decimals.
+pub(crate) fn format_duration(duration: std::time::Duration) -> String {
+    format!("{:.2}s", duration.as_secs_f64())
+}
+
+/// Format the header and the rows of a table with its columns aligned.
+fn format_table(header: &[&str], rows: &[Vec<String>]) -> Vec<String> {
+    let mut widths = header.iter().map(|cell| cell.len()).collect::<Vec<_>>();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let format_row = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    std::iter::once(format_row(header.to_vec()))
+        .chain(
+            rows.iter()
+                .map(|row| format_row(row.iter().map(String::as_str).collect())),
+        )
+        .collect()
+}
+
+/// Print a table with the status of each crate in each runtime when testing
+/// a matrix of runtimes. Crates not tested in a runtime are marked with `-`.
+fn print_matrix(root: &Path, crate_runs: &[CrateRun]) {
+    let mut runtimes = Vec::new();
+    let mut crates_paths = Vec::new();
+    for crate_run in crate_runs {
+        if let Some(runtime) = &crate_run.runtime {
+            if !runtimes.contains(&runtime.as_str()) {
+                runtimes.push(runtime.as_str());
+            }
+        }
+        if !crates_paths.contains(&&crate_run.crate_path) {
+            crates_paths.push(&crate_run.crate_path);
+        }
+    }
+
+    let header = std::iter::once("CRATE")
+        .chain(runtimes.iter().copied())
+        .collect::<Vec<_>>();
+    let rows = crates_paths
+        .iter()
+        .map(|crate_path| {
+            std::iter::once(display_crate_path(root, crate_path))
+                .chain(runtimes.iter().map(|runtime| {
+                    crate_runs
+                        .iter()
+                        .find(|crate_run| {
+                            crate_run.crate_path == **crate_path
+                                && crate_run.runtime.as_deref() == Some(runtime)
+                        })
+                        .map_or("-", |crate_run| crate_run.status.as_str())
+                        .to_string()
+                }))
+                .collect()
+        })
+        .collect::<Vec<_>>();
+
+    print_to_stdout!("Runtimes matrix:");
+    for line in format_table(&header, &rows) {
+        print_to_stdout!("  {}", line);
+    }
+}
+
+/// Print a table with the result of each crate, followed by the list of
+/// crates that failed, if any. When testing a matrix of runtimes, each row
+/// is the run of a crate in a runtime and a matrix with the status of each
+/// crate in each runtime is printed too.
+///
+/// The total `duration` is the elapsed time of the whole run, which is less
+/// than the sum of the durations of the crates when they run in parallel.
+pub(crate) fn print_summary(root: &Path, crate_runs: &[CrateRun], duration: std::time::Duration) {
+    let matrix = crate_runs
+        .iter()
+        .any(|crate_run| crate_run.runtime.is_some());
+    let mut header = vec![
+        "CRATE",
+        "STATUS",
+        "EXIT CODE",
+        "PASSED",
+        "FAILED",
+        "IGNORED",
+        "DURATION",
+    ];
+    if matrix {
+        header.insert(1, "RUNTIME");
+    }
+    let mut total_counts = TestCounts::default();
+    let rows = crate_runs
+        .iter()
+        .map(|crate_run| {
+            let counts = crate_run.test_counts.map(|counts| {
+                total_counts += counts;
+                [counts.passed, counts.failed, counts.ignored].map(|count| count.to_string())
+            });
+            let [passed, failed, ignored] =
+                counts.unwrap_or_else(|| ["-", "-", "-"].map(String::from));
+            let mut row = vec![
+                display_crate_path(root, &crate_run.crate_path),
+                crate_run.status.as_str().to_string(),
+                crate_run
+                    .exit_code
+                    .map(|code| code.to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+                passed,
+                failed,
+                ignored,
+                format_duration(crate_run.duration),
+            ];
+            if matrix {
+                row.insert(1, crate_run.runtime.clone().unwrap_or_default());
+            }
+            row
+        })
+        .collect::<Vec<_>>();
+
+    print_to_stdout!("Summary:");
+    for line in format_table(&header, &rows) {
+        print_to_stdout!("  {}", line);
+    }
+    if matrix {
+        print_matrix(root, crate_runs);
+    }
+    print_to_stdout!(
+        "Total: {} passed, {} failed, {} ignored in {} {} ({}).",
+        total_counts.passed,
+        total_counts.failed,
+        total_counts.ignored,
+        crate_runs.len(),
+        if matrix { "runs" } else { "crates" },
+        format_duration(duration)
+    );
+
+    let print_crates_with_status = |title: &str, status: CrateStatus| {
+        let crates = crate_runs
+            .iter()
+            .filter(|crate_run| crate_run.status == status)
+            .collect::<Vec<_>>();
+        if !crates.is_empty() {
+            print_to_stderr!("{}", title);
+            for crate_run in crates {
+                print_to_stderr!("  - {}", crate_run.display_name(root));
+            }
+        }
+    };
+    let flaky = crate_runs
+        .iter()
+        .filter(|crate_run| crate_run.status == CrateStatus::Flaky)
+        .collect::<Vec<_>>();
+    if !flaky.is_empty() {
+        print_to_stderr!("Flaky crates:");
+        for crate_run in flaky {
+            print_to_stderr!(
+                "  - {} (passed on attempt {})",
+                crate_run.display_name(root),
+                crate_run.attempts
+            );
+        }
+    }
+    print_crates_with_status("Crates cancelled while running:", CrateStatus::Cancelled);
+    print_crates_with_status("Crates that never ran:", CrateStatus::Skipped);
+    print_crates_with_status("Crates that timed out:", CrateStatus::TimedOut);
+    print_crates_with_status("Failed crates:", CrateStatus::Failed);
+}
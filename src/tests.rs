@@ -56,6 +56,7 @@ fn create_crates_with_librs(dir: &TempDir, names_and_contents: &[(&str, &str)])
     for (name, content) in names_and_contents {
         let crate_dir = dir.path().join(name);
         std::fs::create_dir(&crate_dir).unwrap();
+        let name = name.rsplit('/').next().unwrap();
         std::fs::write(
             crate_dir.join("Cargo.toml"),
             format!(
@@ -74,6 +75,92 @@ wasm-bindgen-test = {{ version = ">=0.3", default-features = false, features = [
     }
 }
 
+/// Script body of a fake `wasm-pack` that prints the name of the crate
+/// to test and fails for crates named `bar`.
+#[cfg(unix)]
+static FAKE_WASM_PACK_FAILING_FOR_BAR: &str = r#"
+for arg in "$@"; do
+    if [ -d "$arg" ]; then crate=$(basename "$arg"); fi
+done
+echo "running $crate"
+[ "$crate" != "bar" ]
+"#;
+
+/// Script body of a fake `wasm-pack` that prints libtest-like output as
+/// `wasm-bindgen-test-runner` does. The crate `bar` has a failing test.
+#[cfg(unix)]
+static FAKE_WASM_PACK_WITH_TEST_OUTPUT: &str = r#"
+for arg in "$@"; do
+    if [ -d "$arg" ]; then crate=$(basename "$arg"); fi
+done
+echo "running 2 tests"
+echo "test ${crate}::passes ... ok"
+if [ "$crate" = "bar" ]; then
+    echo "test bar::fails ... FAILED"
+    echo ""
+    echo "failures:"
+    echo ""
+    echo "---- bar::fails output ----"
+    echo "    panicked at 'assertion failed: 1 < 2 && 2 > 1'"
+    echo ""
+    echo "failures:"
+    echo "    bar::fails"
+    echo ""
+    echo "test result: FAILED. 1 passed; 1 failed; 0 ignored; 0 filtered out; finished in 0.01s"
+    exit 1
+fi
+echo "test foo::ignored ... ignored"
+echo ""
+echo "test result: ok. 1 passed; 0 failed; 1 ignored; 0 filtered out; finished in 0.01s"
+"#;
+
+/// Script body of a fake `wasm-pack` that fails the first time that the
+/// crate `bar` is tested and passes the next ones.
+#[cfg(unix)]
+static FAKE_WASM_PACK_FLAKY_BAR: &str = r#"
+for arg in "$@"; do
+    if [ -d "$arg" ]; then crate_path="$arg"; fi
+done
+if [ "$(basename "$crate_path")" = "bar" ] && [ ! -f "$crate_path/attempted" ]; then
+    touch "$crate_path/attempted"
+    exit 1
+fi
+"#;
+
+/// Create a fake `wasm-pack` executable running `script` with `sh` and
+/// prepend its directory to the `PATH` of the command.
+#[cfg(unix)]
+fn use_fake_wasm_pack(cmd: &mut assert_cmd::Command, dir: &TempDir, script: &str) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let bin_dir = dir.path().join("fake-bin");
+    std::fs::create_dir_all(&bin_dir).unwrap();
+    let wasm_pack_path = bin_dir.join("wasm-pack");
+    std::fs::write(&wasm_pack_path, format!("#!/bin/sh\n{script}")).unwrap();
+    std::fs::set_permissions(&wasm_pack_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = std::env::var("PATH").unwrap_or_default();
+    cmd.env("PATH", format!("{}:{}", bin_dir.display(), path));
+}
+
+fn create_foo_and_bar_crates(dir: &TempDir) {
+    #[cfg(feature = "workspace")]
+    create_cargo_toml_for_workspace(dir, &["foo", "bar"]);
+
+    create_crates_with_librs(
+        dir,
+        &[
+            (
+                "foo",
+                "use wasm_bindgen_test::*;\n\n#[wasm_bindgen_test]\nfn foo() {}\n",
+            ),
+            (
+                "bar",
+                "use wasm_bindgen_test::*;\n\n#[wasm_bindgen_test]\nfn bar() {}\n",
+            ),
+        ],
+    );
+}
+
 #[test]
 fn help_option_prints_help_to_stderr_and_exitcode_1() {
     let dir = tempdir();
@@ -330,3 +417,1737 @@ fn tests_pass_with_current_directory() {
         stdout_stderr
     );
 }
+
+#[test]
+fn invalid_jobs_value() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["--jobs", "zero"]);
+
+    let output = cmd.output().unwrap();
+    assert_eq!(output.status.code(), Some(8));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Invalid value for option --jobs (found zero)."),
+        "{}",
+        stderr
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn parallel_jobs_prefix_output_and_aggregate_failures() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    use_fake_wasm_pack(&mut cmd, &dir, FAKE_WASM_PACK_FAILING_FOR_BAR);
+    cmd.args(["-j", "2", "--node"]);
+    create_foo_and_bar_crates(&dir);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert_eq!(output.status.code(), Some(6), "{}", stdout_stderr);
+    assert!(stdout.contains("[foo] running foo"), "{}", stdout_stderr);
+    assert!(stdout.contains("[bar] running bar"), "{}", stdout_stderr);
+    assert!(stderr.contains("Some tests failed."), "{}", stdout_stderr);
+}
+
+#[cfg(unix)]
+#[test]
+fn summary_table_lists_each_crate_and_failed_crates() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    use_fake_wasm_pack(&mut cmd, &dir, FAKE_WASM_PACK_FAILING_FOR_BAR);
+    cmd.arg("--node");
+    create_foo_and_bar_crates(&dir);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert_eq!(output.status.code(), Some(6), "{}", stdout_stderr);
+    assert!(
+        stdout.contains(
+            "[wasm-pack-test-all]   CRATE  STATUS  EXIT CODE  PASSED  FAILED  IGNORED  DURATION"
+        ),
+        "{}",
+        stdout_stderr
+    );
+    let row = |name: &str| {
+        stdout
+            .lines()
+            .find(|line| line.starts_with(&format!("[wasm-pack-test-all]   {name} ")))
+            .unwrap_or_else(|| panic!("{}", stdout_stderr))
+            .split_whitespace()
+            .skip(1)
+            .take(3)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(row("foo"), ["foo", "passed", "0"], "{}", stdout_stderr);
+    assert_eq!(row("bar"), ["bar", "failed", "1"], "{}", stdout_stderr);
+    let failed_crates = stderr.split("Failed crates:").nth(1).unwrap_or_default();
+    assert!(failed_crates.contains("  - bar"), "{}", stdout_stderr);
+    assert!(!failed_crates.contains("  - foo"), "{}", stdout_stderr);
+}
+
+#[cfg(unix)]
+#[test]
+fn parallel_jobs_get_their_own_test_server_address() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    use_fake_wasm_pack(
+        &mut cmd,
+        &dir,
+        "echo \"address=$WASM_BINDGEN_TEST_ADDRESS\"",
+    );
+    cmd.env_remove("WASM_BINDGEN_TEST_ADDRESS");
+    cmd.args(["--jobs=2", "--chrome", "--headless"]);
+    create_foo_and_bar_crates(&dir);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    let addresses = stdout
+        .lines()
+        .filter_map(|line| line.split_once("address=").map(|(_, address)| address))
+        .collect::<Vec<_>>();
+    assert_eq!(addresses.len(), 2, "{}", stdout_stderr);
+    assert!(addresses[0].starts_with("127.0.0.1:"), "{}", stdout_stderr);
+    assert_ne!(addresses[0], addresses[1], "{}", stdout_stderr);
+}
+
+#[cfg(unix)]
+#[test]
+fn junit_report_has_a_testsuite_per_crate_and_a_testcase_per_test() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    use_fake_wasm_pack(&mut cmd, &dir, FAKE_WASM_PACK_WITH_TEST_OUTPUT);
+    let report_path = dir.path().join("junit.xml");
+    cmd.args(["--node", "--junit", report_path.to_str().unwrap()]);
+    create_foo_and_bar_crates(&dir);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert_eq!(output.status.code(), Some(6), "{}", stdout_stderr);
+
+    let report = std::fs::read_to_string(&report_path).unwrap();
+    assert!(
+        report.contains(
+            r#"<testsuites name="wasm-pack-test-all" tests="4" failures="1" errors="0" skipped="1""#
+        ),
+        "{}",
+        report
+    );
+    assert!(
+        report.contains(r#"<testsuite name="foo" tests="2" failures="0" errors="0" skipped="1""#),
+        "{}",
+        report
+    );
+    assert!(
+        report.contains(r#"<testsuite name="bar" tests="2" failures="1" errors="0" skipped="0""#),
+        "{}",
+        report
+    );
+    assert!(
+        report.contains(r#"<testcase name="foo::passes" classname="foo"/>"#),
+        "{}",
+        report
+    );
+    assert!(
+        report.contains(
+            r#"<testcase name="bar::fails" classname="bar">
+      <failure message="test failed">    panicked at &apos;assertion failed: 1 &lt; 2 &amp;&amp; 2 &gt; 1&apos;"#
+        ),
+        "{}",
+        report
+    );
+    assert!(report.contains("<system-out>running 2 tests"), "{}", report);
+}
+
+#[cfg(unix)]
+#[test]
+fn summary_total_duration_is_the_elapsed_time_of_the_run() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    use_fake_wasm_pack(&mut cmd, &dir, "sleep 1");
+    cmd.args(["-j", "2", "--node"]);
+    create_foo_and_bar_crates(&dir);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    // the crates run in parallel, so the run takes less than their sum
+    let total_seconds = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("[wasm-pack-test-all] Total: "))
+        .and_then(|line| {
+            line.rsplit_once('(')?
+                .1
+                .strip_suffix("s).")?
+                .parse::<f64>()
+                .ok()
+        })
+        .unwrap_or_else(|| panic!("{}", stdout_stderr));
+    assert!(total_seconds < 1.9, "{}", stdout_stderr);
+}
+
+#[cfg(unix)]
+#[test]
+fn summary_shows_test_counts_per_crate_and_totals() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    use_fake_wasm_pack(&mut cmd, &dir, FAKE_WASM_PACK_WITH_TEST_OUTPUT);
+    cmd.arg("--node");
+    create_foo_and_bar_crates(&dir);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert_eq!(output.status.code(), Some(6), "{}", stdout_stderr);
+    let row = |name: &str| {
+        stdout
+            .lines()
+            .find(|line| line.starts_with(&format!("[wasm-pack-test-all]   {name} ")))
+            .unwrap_or_else(|| panic!("{}", stdout_stderr))
+            .split_whitespace()
+            .skip(1)
+            .take(6)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        row("foo"),
+        ["foo", "passed", "0", "1", "0", "1"],
+        "{}",
+        stdout_stderr
+    );
+    assert_eq!(
+        row("bar"),
+        ["bar", "failed", "1", "1", "1", "0"],
+        "{}",
+        stdout_stderr
+    );
+    assert!(
+        stdout.contains("Total: 2 passed, 1 failed, 1 ignored in 2 crates"),
+        "{}",
+        stdout_stderr
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn message_format_json_prints_events_to_stdout() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    use_fake_wasm_pack(&mut cmd, &dir, FAKE_WASM_PACK_WITH_TEST_OUTPUT);
+    cmd.args(["--message-format", "json", "--node"]);
+    create_foo_and_bar_crates(&dir);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert_eq!(output.status.code(), Some(6), "{}", stdout_stderr);
+    for line in stdout.lines() {
+        assert!(
+            line.starts_with("{\"event\":\"") && line.ends_with('}'),
+            "{}",
+            stdout_stderr
+        );
+    }
+    let events = stdout
+        .lines()
+        .map(|line| line.split('"').nth(3).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events.first(),
+        Some(&"discovery-finished"),
+        "{}",
+        stdout_stderr
+    );
+    assert_eq!(events.last(), Some(&"run-finished"), "{}", stdout_stderr);
+    for event in ["crate-started", "crate-finished", "test-result"] {
+        assert_eq!(
+            events.iter().filter(|e| **e == event).count(),
+            2,
+            "{}",
+            stdout_stderr
+        );
+    }
+    let bar_path = dir.path().join("bar").display().to_string();
+    assert!(
+        stdout.contains(&format!(
+            r#"{{"event":"test-result","crate":"{bar_path}","passed":1,"failed":1,"ignored":0}}"#
+        )),
+        "{}",
+        stdout_stderr
+    );
+    assert!(
+        stdout.contains(r#""event":"run-finished","status":"failed","exit_code":6,"crates":2,"passed":2,"failed":1,"ignored":1}"#),
+        "{}",
+        stdout_stderr
+    );
+    assert!(
+        stderr.contains("test bar::fails ... FAILED"),
+        "{}",
+        stdout_stderr
+    );
+    assert!(stderr.contains("Summary:"), "{}", stdout_stderr);
+}
+
+#[test]
+fn list_prints_planned_commands_without_running_them() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    // wasm-pack is not needed as nothing is executed
+    cmd.env("PATH", "");
+    cmd.args(["--list", "--node", "--", "--features", "a b"]);
+    create_foo_and_bar_crates(&dir);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    for name in ["foo", "bar"] {
+        let crate_path = dir.path().join(name).display().to_string();
+        assert!(
+            stdout.contains(&format!(
+                "    + wasm-pack test --node {crate_path} -- --features 'a b'"
+            )),
+            "{}",
+            stdout_stderr
+        );
+    }
+    assert!(!stdout.contains("Summary:"), "{}", stdout_stderr);
+}
+
+#[test]
+fn dry_run_with_message_format_json_prints_planned_commands_as_events() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    cmd.env("PATH", "");
+    cmd.args(["--dry-run", "--message-format=json", "--node"]);
+    create_foo_and_bar_crates(&dir);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    let foo_path = dir.path().join("foo").display().to_string();
+    assert!(
+        stdout.contains(&format!(
+            r#"{{"event":"crate-planned","crate":"{foo_path}","command":"wasm-pack test --node {foo_path}","args":["wasm-pack","test","--node","{foo_path}"]}}"#
+        )),
+        "{}",
+        stdout_stderr
+    );
+    assert_eq!(stdout.lines().count(), 3, "{}", stdout_stderr);
+}
+
+#[cfg(unix)]
+#[test]
+fn fail_fast_skips_remaining_crates() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    use_fake_wasm_pack(&mut cmd, &dir, "exit 1");
+    cmd.args(["--fail-fast", "--node"]);
+    create_foo_and_bar_crates(&dir);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert_eq!(output.status.code(), Some(6), "{}", stdout_stderr);
+    assert_eq!(
+        stdout.matches("+ wasm-pack test").count(),
+        1,
+        "{}",
+        stdout_stderr
+    );
+    assert!(
+        stderr.contains("Crates that never ran:"),
+        "{}",
+        stdout_stderr
+    );
+    assert!(stdout.contains(" skipped "), "{}", stdout_stderr);
+}
+
+#[cfg(unix)]
+#[test]
+fn fail_fast_kills_running_crates() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    use_fake_wasm_pack(
+        &mut cmd,
+        &dir,
+        r#"
+for arg in "$@"; do
+    if [ -d "$arg" ]; then crate=$(basename "$arg"); fi
+done
+if [ "$crate" = "bar" ]; then exit 1; fi
+exec sleep 30
+"#,
+    );
+    cmd.args(["--fail-fast", "-j", "2", "--node"]);
+    create_foo_and_bar_crates(&dir);
+
+    let start = std::time::Instant::now();
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(start.elapsed().as_secs() < 20, "{}", stdout_stderr);
+    assert_eq!(output.status.code(), Some(6), "{}", stdout_stderr);
+    let cancelled = stderr
+        .split("Crates cancelled while running:")
+        .nth(1)
+        .unwrap_or_else(|| panic!("{}", stdout_stderr));
+    assert!(cancelled.contains("  - foo"), "{}", stdout_stderr);
+}
+
+#[test]
+fn invalid_timeout_value() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["--timeout", "5 minutes"]);
+
+    let output = cmd.output().unwrap();
+    assert_eq!(output.status.code(), Some(8));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Invalid value for option --timeout (found 5 minutes)."),
+        "{}",
+        stderr
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn timeout_kills_process_tree_of_crate() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    // the sleeps run in grandchild processes that keep the output pipes
+    // open, and new ones keep being spawned while the tree is killed
+    use_fake_wasm_pack(
+        &mut cmd,
+        &dir,
+        r#"
+for arg in "$@"; do
+    if [ -d "$arg" ]; then crate=$(basename "$arg"); fi
+done
+if [ "$crate" = "bar" ]; then
+    while true; do sh -c "sleep 30" & sleep 0.1; done
+fi
+"#,
+    );
+    cmd.args(["--timeout", "1s", "--node"]);
+    create_foo_and_bar_crates(&dir);
+
+    let start = std::time::Instant::now();
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(start.elapsed().as_secs() < 20, "{}", stdout_stderr);
+    assert_eq!(output.status.code(), Some(9), "{}", stdout_stderr);
+    assert!(
+        stdout.contains("[wasm-pack-test-all]   bar    timeout "),
+        "{}",
+        stdout_stderr
+    );
+    assert!(
+        stdout.contains("[wasm-pack-test-all]   foo    passed "),
+        "{}",
+        stdout_stderr
+    );
+    let timed_out = stderr
+        .split("Crates that timed out:")
+        .nth(1)
+        .unwrap_or_else(|| panic!("{}", stdout_stderr));
+    assert!(timed_out.contains("  - bar"), "{}", stdout_stderr);
+}
+
+#[cfg(unix)]
+#[test]
+fn timeout_keeps_wasm_pack_in_the_process_group() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    // a Ctrl-C in the terminal only reaches the foreground process group
+    use_fake_wasm_pack(
+        &mut cmd,
+        &dir,
+        r#"[ "$(ps -o pgid= -p $$)" = "$(ps -o pgid= -p $PPID)" ]"#,
+    );
+    cmd.args(["--timeout", "10s", "--node"]);
+    create_foo_and_bar_crates(&dir);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+}
+
+#[cfg(unix)]
+#[test]
+fn retries_report_crates_passing_on_retry_as_flaky() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    use_fake_wasm_pack(&mut cmd, &dir, FAKE_WASM_PACK_FLAKY_BAR);
+    cmd.args(["--retries", "2", "--node"]);
+    create_foo_and_bar_crates(&dir);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert_eq!(
+        stdout.matches("+ wasm-pack test").count(),
+        3,
+        "{}",
+        stdout_stderr
+    );
+    assert!(
+        stdout.contains("[wasm-pack-test-all]   bar    flaky "),
+        "{}",
+        stdout_stderr
+    );
+    assert!(
+        stderr.contains("Flaky crates:\n[wasm-pack-test-all]   - bar (passed on attempt 2)"),
+        "{}",
+        stdout_stderr
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn fail_on_flaky_makes_flaky_crates_fail_the_run() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    use_fake_wasm_pack(&mut cmd, &dir, FAKE_WASM_PACK_FLAKY_BAR);
+    cmd.args(["--retries=1", "--fail-on-flaky", "--node"]);
+    create_foo_and_bar_crates(&dir);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert_eq!(output.status.code(), Some(6), "{}", stdout_stderr);
+    assert!(stderr.contains("Flaky crates:"), "{}", stdout_stderr);
+}
+
+#[test]
+fn wasm_bindgen_test_attributes_are_detected_in_all_their_forms() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    cmd.arg("--list");
+
+    create_crates_with_librs(
+        &dir,
+        &[
+            (
+                "with_arguments",
+                "#[wasm_bindgen_test(unsupported = test)]\nfn foo() {}\n",
+            ),
+            (
+                "with_path",
+                "#[wasm_bindgen_test::wasm_bindgen_test]\nfn foo() {}\n",
+            ),
+            (
+                "with_cfg_attr",
+                "#[cfg_attr(target_arch = \"wasm32\", wasm_bindgen_test)]\nfn foo() {}\n",
+            ),
+            (
+                "with_renamed_import",
+                "use wasm_bindgen_test::{wasm_bindgen_test as wasm_test};\n\n#[wasm_test]\nfn foo() {}\n",
+            ),
+            (
+                "after_escaped_backslash",
+                "fn f(c: char) -> bool {\n    c == '\\\\'\n}\n\n#[wasm_bindgen_test]\nfn works() {}\n",
+            ),
+            (
+                "after_escaped_quote",
+                "fn f(c: char) -> bool {\n    c == '\\''\n}\n\n#[wasm_bindgen_test]\nfn works() {}\n",
+            ),
+            (
+                "in_comments",
+                "// #[wasm_bindgen_test]\n/* /* nested */ #[wasm_bindgen_test] */\n/// #[wasm_bindgen_test]\n#[test]\nfn foo() {}\n",
+            ),
+            (
+                "in_strings",
+                "const A: &str = \"#[wasm_bindgen_test]\";\nconst B: &str = r#\"\n#[wasm_bindgen_test]\"#;\nconst C: char = '#';\n",
+            ),
+        ],
+    );
+    std::fs::write(
+        dir.path().join("in_comments").join("README.md"),
+        "#[wasm_bindgen_test]\n",
+    )
+    .unwrap();
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    for name in [
+        "with_arguments",
+        "with_path",
+        "with_cfg_attr",
+        "with_renamed_import",
+        "after_escaped_backslash",
+        "after_escaped_quote",
+    ] {
+        let crate_path = dir.path().join(name).display().to_string();
+        assert!(
+            stdout.contains(&format!("  - {crate_path}\n")),
+            "{}",
+            stdout_stderr
+        );
+    }
+    for name in ["in_comments", "in_strings"] {
+        let crate_path = dir.path().join(name).display().to_string();
+        assert!(
+            !stdout.contains(&format!("  - {crate_path}\n")),
+            "{}",
+            stdout_stderr
+        );
+    }
+}
+
+#[test]
+fn discovery_respects_crate_boundaries_and_skips_build_directories() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    cmd.arg("--list");
+
+    create_crates_with_librs(&dir, &[("parent", "pub fn foo() {}\n")]);
+    let parent_dir = dir.path().join("parent");
+    // a nested crate with tests doesn't make its parent testable
+    create_crates_with_librs(
+        &dir,
+        &[("parent/child", "#[wasm_bindgen_test]\nfn foo() {}\n")],
+    );
+    // sources in build directories are not taken into account
+    std::fs::create_dir_all(parent_dir.join("target").join("debug")).unwrap();
+    std::fs::write(
+        parent_dir.join("target").join("debug").join("build.rs"),
+        "#[wasm_bindgen_test]\nfn foo() {}\n",
+    )
+    .unwrap();
+    // crates in dependency directories are not discovered
+    std::fs::create_dir(dir.path().join("node_modules")).unwrap();
+    create_crates_with_librs(
+        &dir,
+        &[(
+            "node_modules/vendored",
+            "#[wasm_bindgen_test]\nfn foo() {}\n",
+        )],
+    );
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert!(
+        stdout.contains("Found 1 crates in the directory"),
+        "{}",
+        stdout_stderr
+    );
+    let child_path = parent_dir.join("child").display().to_string();
+    assert!(
+        stdout.contains(&format!("  - {child_path}\n")),
+        "{}",
+        stdout_stderr
+    );
+}
+
+#[test]
+fn discovery_honors_ignore_files_and_exclude_option() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["--list", "--exclude", "legacy*"]);
+
+    let with_tests = "#[wasm_bindgen_test]\nfn foo() {}\n";
+    std::fs::create_dir(dir.path().join("examples")).unwrap();
+    create_crates_with_librs(
+        &dir,
+        &[
+            ("kept", with_tests),
+            ("generated", with_tests),
+            ("legacy-foo", with_tests),
+            ("examples/slow", with_tests),
+            ("examples/fast", with_tests),
+        ],
+    );
+    std::fs::write(
+        dir.path().join(".gitignore"),
+        "# build output\n/generated/\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path()
+            .join("examples")
+            .join(".wasm-pack-test-all-ignore"),
+        "/*\n!/fast/\n",
+    )
+    .unwrap();
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert!(
+        stdout.contains("Found 2 crates in the directory"),
+        "{}",
+        stdout_stderr
+    );
+    for kept in [
+        dir.path().join("examples").join("fast"),
+        dir.path().join("kept"),
+    ] {
+        assert!(
+            stdout.contains(&format!("  - {}\n", kept.display())),
+            "{}",
+            stdout_stderr
+        );
+    }
+}
+
+#[test]
+fn ignore_files_of_parent_directories_stop_at_the_repository_root() {
+    let dir = tempdir();
+    std::fs::create_dir(dir.path().join(".git")).unwrap();
+    std::fs::write(dir.path().join(".gitignore"), "a\n").unwrap();
+    std::fs::create_dir(dir.path().join("inner")).unwrap();
+    create_crates_with_librs(&dir, &[("inner/a", "#[wasm_bindgen_test]\nfn foo() {}\n")]);
+
+    // The ignore files of the repository apply to the tested directory
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["inner", "--list"]);
+    let output = cmd.output().unwrap();
+    assert_eq!(output.status.code(), Some(4));
+
+    // but not when it is the root of another repository
+    std::fs::create_dir(dir.path().join("inner").join(".git")).unwrap();
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["inner", "--list"]);
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert!(stdout.contains("  - inner/a\n"), "{}", stdout_stderr);
+}
+
+#[cfg(feature = "workspace")]
+#[test]
+fn workspace_members_globs_are_expanded_and_exclude_is_honored() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    cmd.arg("--list");
+
+    std::fs::write(
+        dir.path().join("Cargo.toml"),
+        "[workspace]\nmembers = [\"crates/*\", \"tools/cli\"]\nexclude = [\"crates/legacy\"]\n",
+    )
+    .unwrap();
+    let with_tests = "#[wasm_bindgen_test]\nfn foo() {}\n";
+    std::fs::create_dir_all(dir.path().join("crates").join("docs")).unwrap();
+    std::fs::create_dir(dir.path().join("tools")).unwrap();
+    create_crates_with_librs(
+        &dir,
+        &[
+            ("crates/foo", with_tests),
+            ("crates/bar", with_tests),
+            ("crates/legacy", with_tests),
+            ("tools/cli", with_tests),
+        ],
+    );
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert!(
+        stdout.contains("Found 3 crates in the workspace"),
+        "{}",
+        stdout_stderr
+    );
+    for member in ["crates/bar", "crates/foo", "tools/cli"] {
+        let member_path = dir.path().join(member).display().to_string();
+        assert!(
+            stdout.contains(&format!("  - {member_path}\n")),
+            "{}",
+            stdout_stderr
+        );
+    }
+}
+
+#[cfg(feature = "workspace")]
+#[test]
+fn workspace_members_inside_excluded_directories_are_excluded() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["--list", "--exclude", "crates"]);
+
+    std::fs::write(
+        dir.path().join("Cargo.toml"),
+        "[workspace]\nmembers = [\"crates/*\", \"tools/cli\"]\n",
+    )
+    .unwrap();
+    let with_tests = "#[wasm_bindgen_test]\nfn foo() {}\n";
+    std::fs::create_dir(dir.path().join("crates")).unwrap();
+    std::fs::create_dir(dir.path().join("tools")).unwrap();
+    create_crates_with_librs(
+        &dir,
+        &[
+            ("crates/foo", with_tests),
+            ("crates/bar", with_tests),
+            ("tools/cli", with_tests),
+        ],
+    );
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert!(
+        stdout.contains("Found 1 crates in the workspace"),
+        "{}",
+        stdout_stderr
+    );
+    assert!(
+        stdout.contains(&format!(
+            "  - {}\n",
+            dir.path().join("tools").join("cli").display()
+        )),
+        "{}",
+        stdout_stderr
+    );
+    for member in ["crates/bar", "crates/foo"] {
+        let member_path = dir.path().join(member).display().to_string();
+        assert!(!stdout.contains(&member_path), "{}", stdout_stderr);
+    }
+}
+
+#[cfg(feature = "workspace")]
+#[test]
+fn workspace_members_glob_matching_nothing_is_an_error() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    cmd.arg("--list");
+
+    std::fs::write(
+        dir.path().join("Cargo.toml"),
+        "[workspace]\nmembers = [\"crates/*\"]\n",
+    )
+    .unwrap();
+
+    let output = cmd.output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("The workspace member pattern crates/* does not match any crate"),
+        "{}",
+        stderr
+    );
+}
+
+#[cfg(feature = "workspace")]
+#[test]
+fn workspace_members_without_wasm_tests_are_skipped() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    cmd.arg("--list");
+
+    create_cargo_toml_for_workspace(&dir, &["foo", "native"]);
+    create_crates_with_librs(
+        &dir,
+        &[
+            ("foo", "#[wasm_bindgen_test]\nfn foo() {}\n"),
+            ("native", "#[test]\nfn native() {}\n"),
+        ],
+    );
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert!(
+        stdout.contains("Found 1 crates in the workspace"),
+        "{}",
+        stdout_stderr
+    );
+    assert!(
+        stdout.contains("  - native (skipped: no wasm_bindgen_test found)\n"),
+        "{}",
+        stdout_stderr
+    );
+    let native_path = dir.path().join("native").display().to_string();
+    assert!(
+        !stdout.contains(&format!("+ wasm-pack test {native_path}")),
+        "{}",
+        stdout_stderr
+    );
+}
+
+#[cfg(feature = "workspace")]
+#[test]
+fn workspace_root_is_discovered_from_inside_a_member() {
+    let dir = tempdir();
+    create_foo_and_bar_crates(&dir);
+    let foo_path = dir.path().join("foo");
+
+    let mut cmd = build_cmd();
+    cmd.current_dir(foo_path.join("src"));
+    cmd.arg("--list");
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert!(
+        stdout.contains(&format!(
+            "Found 1 crates in the workspace {}",
+            dir.path().display()
+        )),
+        "{}",
+        stdout_stderr
+    );
+    assert!(
+        stdout.contains(&format!("+ wasm-pack test {}", foo_path.display())),
+        "{}",
+        stdout_stderr
+    );
+
+    let mut cmd = build_cmd();
+    cmd.current_dir(foo_path.join("src"));
+    cmd.args(["--list", "--workspace"]);
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert!(
+        stdout.contains("Found 2 crates in the workspace"),
+        "{}",
+        stdout_stderr
+    );
+}
+
+#[cfg(feature = "workspace")]
+#[test]
+fn workspace_root_is_discovered_following_package_workspace() {
+    let dir = tempdir();
+    let workspace_path = dir.path().join("workspace");
+    std::fs::create_dir(&workspace_path).unwrap();
+    std::fs::write(
+        workspace_path.join("Cargo.toml"),
+        "[workspace]\nmembers = [\"../app\"]\n",
+    )
+    .unwrap();
+    create_crates_with_librs(&dir, &[("app", "#[wasm_bindgen_test]\nfn foo() {}\n")]);
+    let app_manifest = dir.path().join("app").join("Cargo.toml");
+    let manifest = std::fs::read_to_string(&app_manifest).unwrap();
+    std::fs::write(
+        &app_manifest,
+        manifest.replace("[package]\n", "[package]\nworkspace = \"../workspace\"\n"),
+    )
+    .unwrap();
+
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["--list", dir.path().join("app").to_str().unwrap()]);
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert!(
+        stdout.contains(&format!(
+            "Found 1 crates in the workspace {}",
+            workspace_path.display()
+        )),
+        "{}",
+        stdout_stderr
+    );
+}
+
+#[cfg(feature = "workspace")]
+#[test]
+fn workspace_metadata_provides_defaults() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    cmd.arg("--list");
+
+    let manifest =
+        "[workspace]\nmembers = [\"crates/*\"]\n\n[workspace.metadata.wasm-pack-test-all]\n";
+    std::fs::write(
+        dir.path().join("Cargo.toml"),
+        format!("{manifest}runtimes = [\"node\"]\njobs = 2\ntimeout = \"5m\"\nexclude = [\"crates/legacy\"]\nwasm-pack-args = [\"--release\"]\ncargo-args = [\"--locked\"]\n"),
+    )
+    .unwrap();
+    let with_tests = "#[wasm_bindgen_test]\nfn foo() {}\n";
+    std::fs::create_dir(dir.path().join("crates")).unwrap();
+    create_crates_with_librs(
+        &dir,
+        &[("crates/foo", with_tests), ("crates/legacy", with_tests)],
+    );
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    let foo_path = dir.path().join("crates/foo").display().to_string();
+    assert!(
+        stdout.contains(&format!(
+            "+ wasm-pack test --node --release {foo_path} -- --locked\n"
+        )),
+        "{}",
+        stdout_stderr
+    );
+    assert!(!stdout.contains("legacy"), "{}", stdout_stderr);
+
+    // The options of the command line override the defaults
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["--list", "--chrome", "--", "--offline"]);
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert!(
+        stdout.contains(&format!(
+            "+ wasm-pack test --release --chrome {foo_path} -- --locked --offline\n"
+        )),
+        "{}",
+        stdout_stderr
+    );
+
+    std::fs::write(
+        dir.path().join("Cargo.toml"),
+        format!("{manifest}jobs = 0\n"),
+    )
+    .unwrap();
+    let mut cmd = init_cmd(&dir);
+    cmd.arg("--list");
+    let output = cmd.output().unwrap();
+    assert_eq!(output.status.code(), Some(8));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("`jobs` must be a positive integer"),
+        "{}",
+        stderr
+    );
+}
+
+#[cfg(feature = "workspace")]
+#[test]
+fn crate_configuration_overrides_workspace_default_runtimes() {
+    let dir = tempdir();
+    std::fs::write(
+        dir.path().join("Cargo.toml"),
+        "[workspace]\nmembers = [\"crates/*\"]\n\n[workspace.metadata.wasm-pack-test-all]\nruntimes = [\"chrome\"]\n",
+    )
+    .unwrap();
+    let with_tests = "#[wasm_bindgen_test]\nfn foo() {}\n";
+    std::fs::create_dir(dir.path().join("crates")).unwrap();
+    create_crates_with_librs(
+        &dir,
+        &[
+            ("crates/browser", with_tests),
+            ("crates/node-only", with_tests),
+            ("crates/node-args", with_tests),
+        ],
+    );
+    let add_metadata = |name: &str, metadata: &str| {
+        let manifest_path = dir.path().join("crates").join(name).join("Cargo.toml");
+        let manifest = std::fs::read_to_string(&manifest_path).unwrap();
+        std::fs::write(
+            &manifest_path,
+            format!("{manifest}\n[package.metadata.wasm-pack-test-all]\n{metadata}"),
+        )
+        .unwrap();
+    };
+    add_metadata("node-only", "runtimes = [\"node\"]\n");
+    add_metadata("node-args", "wasm-pack-args = [\"--node\"]\n");
+    let crate_path = |name: &str| dir.path().join("crates").join(name).display().to_string();
+
+    let mut cmd = init_cmd(&dir);
+    cmd.arg("--list");
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    for (name, runtime) in [
+        ("browser", "--chrome"),
+        ("node-only", "--node"),
+        ("node-args", "--node"),
+    ] {
+        assert!(
+            stdout.contains(&format!(
+                "+ wasm-pack test {runtime} {}\n",
+                crate_path(name)
+            )),
+            "{}",
+            stdout_stderr
+        );
+    }
+
+    // The runtimes of the command line are still checked against the ones
+    // allowed by each crate
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["--list", "--firefox"]);
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert!(
+        stdout.contains("  - crates/node-only (skipped: runtime firefox not allowed)\n"),
+        "{}",
+        stdout_stderr
+    );
+}
+
+#[test]
+fn discovery_with_cargo_metadata_tests_workspace_members() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["--list", "--discovery", "cargo-metadata"]);
+
+    std::fs::write(
+        dir.path().join("Cargo.toml"),
+        "[workspace]\nresolver = \"2\"\nmembers = [\"crates/*\"]\nexclude = [\"crates/vendored\"]\n",
+    )
+    .unwrap();
+    std::fs::create_dir(dir.path().join("crates")).unwrap();
+    let with_tests = "#[wasm_bindgen_test]\nfn foo() {}\n";
+    create_crates_with_librs(
+        &dir,
+        &[
+            ("crates/foo", with_tests),
+            ("crates/native", "#[test]\nfn native() {}\n"),
+            ("crates/vendored", with_tests),
+        ],
+    );
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert!(
+        stdout.contains("Found 1 crates in the workspace"),
+        "{}",
+        stdout_stderr
+    );
+    assert!(
+        stdout.contains("  - crates/native (skipped: no wasm_bindgen_test found)\n"),
+        "{}",
+        stdout_stderr
+    );
+    assert!(stdout.contains("crates/foo\n"), "{}", stdout_stderr);
+    assert!(!stdout.contains("vendored"), "{}", stdout_stderr);
+}
+
+#[test]
+fn package_options_select_crates_by_name() {
+    let dir = tempdir();
+    let with_tests = "#[wasm_bindgen_test]\nfn foo() {}\n";
+    create_crates_with_librs(
+        &dir,
+        &[
+            ("app-web", with_tests),
+            ("app-worker", with_tests),
+            ("app-legacy", with_tests),
+            ("core", with_tests),
+        ],
+    );
+
+    let mut cmd = init_cmd(&dir);
+    cmd.args([
+        "--list",
+        "-p",
+        "app-*",
+        "--package=core",
+        "--exclude-package",
+        "*-legacy",
+    ]);
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert!(
+        stdout.contains("Selected 3 of 4 crates by package name."),
+        "{}",
+        stdout_stderr
+    );
+    assert!(!stdout.contains("app-legacy"), "{}", stdout_stderr);
+
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["--list", "-p", "app-web", "-p", "missing"]);
+    let output = cmd.output().unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("The package missing was not found in the discovered crates."),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn changed_since_tests_changed_crates_and_their_dependents() {
+    let dir = tempdir();
+    let with_tests = "#[wasm_bindgen_test]\nfn foo() {}\n";
+    create_crates_with_librs(
+        &dir,
+        &[
+            ("core", with_tests),
+            ("app", with_tests),
+            ("worker", with_tests),
+            ("other", with_tests),
+        ],
+    );
+    let add_to_manifest = |name: &str, extra: &str| {
+        let manifest_path = dir.path().join(name).join("Cargo.toml");
+        let manifest = std::fs::read_to_string(&manifest_path).unwrap();
+        std::fs::write(&manifest_path, format!("{manifest}{extra}")).unwrap();
+    };
+    add_to_manifest("app", "core = { path = \"../core\" }\n");
+    add_to_manifest("worker", "\n[dev-dependencies.app]\npath = \"../app\"\n");
+
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(dir.path())
+            .stdout(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    git(&["init", "-q"]);
+    git(&["add", "."]);
+    git(&["commit", "-q", "-m", "Initial commit"]);
+    std::fs::write(
+        dir.path().join("core").join("src").join("lib.rs"),
+        format!("{with_tests}\npub fn changed() {{}}\n"),
+    )
+    .unwrap();
+
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["--list", "--changed-since", "HEAD"]);
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert!(
+        stdout.contains("Selected 3 of 4 crates with changes since HEAD."),
+        "{}",
+        stdout_stderr
+    );
+    assert!(!stdout.contains("other"), "{}", stdout_stderr);
+
+    git(&["commit", "-q", "-am", "Change core"]);
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["--list", "--changed-since", "HEAD"]);
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(
+        stdout.contains("No crates changed since HEAD."),
+        "{}",
+        stdout
+    );
+
+    // Untracked files are changes too, unless they are ignored
+    std::fs::write(
+        dir.path().join(".git").join("info").join("exclude"),
+        "*.log\n",
+    )
+    .unwrap();
+    std::fs::write(dir.path().join("worker").join("debug.log"), "").unwrap();
+    std::fs::create_dir(dir.path().join("other").join("tests")).unwrap();
+    std::fs::write(
+        dir.path().join("other").join("tests").join("web.rs"),
+        with_tests,
+    )
+    .unwrap();
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["--list", "--changed-since", "HEAD"]);
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(
+        stdout.contains("Selected 1 of 4 crates with changes since HEAD."),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("worker"), "{}", stdout);
+}
+
+#[test]
+fn files_mode_tests_crates_owning_the_passed_files() {
+    let dir = tempdir();
+    let with_tests = "#[wasm_bindgen_test]\nfn foo() {}\n";
+    create_crates_with_librs(
+        &dir,
+        &[
+            ("core", with_tests),
+            ("app", with_tests),
+            ("other", with_tests),
+        ],
+    );
+    let app_manifest = dir.path().join("app").join("Cargo.toml");
+    let manifest = std::fs::read_to_string(&app_manifest).unwrap();
+    std::fs::write(
+        &app_manifest,
+        format!("{manifest}core = {{ path = \"../core\" }}\n"),
+    )
+    .unwrap();
+
+    // files are passed at the end, like pre-commit does
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["--files", "--list", "--node", "core/src/lib.rs"]);
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert!(
+        stdout.contains("Selected 1 of 3 crates owning the passed files."),
+        "{}",
+        stdout_stderr
+    );
+    let core_path = dir.path().join("core").display().to_string();
+    assert!(
+        stdout.contains(&format!("+ wasm-pack test --node {core_path}\n")),
+        "{}",
+        stdout_stderr
+    );
+
+    // Existing files passed as values of options are not files to test
+    std::fs::write(dir.path().join("chromedriver"), "").unwrap();
+    std::fs::write(dir.path().join("config.toml"), "").unwrap();
+    let mut cmd = init_cmd(&dir);
+    cmd.args([
+        "--files",
+        "--list",
+        "--chrome",
+        "--chromedriver",
+        "chromedriver",
+        "core/src/lib.rs",
+        "--",
+        "--config",
+        "config.toml",
+    ]);
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert!(
+        stdout.contains(&format!(
+            "+ wasm-pack test --chrome --chromedriver chromedriver {core_path} -- --config config.toml\n"
+        )),
+        "{}",
+        stdout_stderr
+    );
+
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["--files", "--with-dependents", "--list", "core/Cargo.toml"]);
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(
+        stdout.contains("Selected 2 of 3 crates owning the passed files."),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn package_metadata_configures_each_crate() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["--list", "--chrome", "--", "--locked"]);
+
+    let with_tests = "#[wasm_bindgen_test]\nfn foo() {}\n";
+    create_crates_with_librs(
+        &dir,
+        &[
+            ("browser", with_tests),
+            ("native-only", with_tests),
+            ("node-only", with_tests),
+            ("plain", with_tests),
+        ],
+    );
+    let add_metadata = |name: &str, metadata: &str| {
+        let manifest_path = dir.path().join(name).join("Cargo.toml");
+        let manifest = std::fs::read_to_string(&manifest_path).unwrap();
+        std::fs::write(
+            &manifest_path,
+            format!("{manifest}\n[package.metadata.wasm-pack-test-all]\n{metadata}"),
+        )
+        .unwrap();
+    };
+    add_metadata(
+        "browser",
+        "wasm-pack-args = [\"--headless\"]\nfeatures = [\"browser-tests\", \"web\"]\nenv = { RUST_LOG = \"debug\" }\n",
+    );
+    add_metadata("native-only", "skip = true\n");
+    add_metadata("node-only", "runtimes = [\"node\"]\n");
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    let browser_path = dir.path().join("browser").display().to_string();
+    assert!(
+        stdout.contains(&format!(
+            "+ RUST_LOG=debug wasm-pack test --chrome --headless {browser_path} -- --features browser-tests,web --locked\n"
+        )),
+        "{}",
+        stdout_stderr
+    );
+    let plain_path = dir.path().join("plain").display().to_string();
+    assert!(
+        stdout.contains(&format!(
+            "+ wasm-pack test --chrome {plain_path} -- --locked\n"
+        )),
+        "{}",
+        stdout_stderr
+    );
+    assert!(
+        stdout.contains("  - native-only (skipped: disabled in package metadata)\n"),
+        "{}",
+        stdout_stderr
+    );
+    assert!(
+        stdout.contains("  - node-only (skipped: runtime chrome not allowed)\n"),
+        "{}",
+        stdout_stderr
+    );
+
+    add_metadata("plain", "unknown = true\n");
+    let mut cmd = init_cmd(&dir);
+    cmd.arg("--list");
+    let output = cmd.output().unwrap();
+    assert_eq!(output.status.code(), Some(8));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("unknown key `unknown`"), "{}", stderr);
+}
+
+#[test]
+fn config_file_profiles_provide_defaults() {
+    let dir = tempdir();
+    let with_tests = "#[wasm_bindgen_test]\nfn foo() {}\n";
+    std::fs::create_dir(dir.path().join("examples")).unwrap();
+    create_crates_with_librs(&dir, &[("app", with_tests), ("examples/demo", with_tests)]);
+    std::fs::write(
+        dir.path().join("wasm-pack-test-all.toml"),
+        "runtimes = [\"node\"]\nexclude = [\"examples/\"]\n\n[profile.ci]\nruntimes = [\"chrome\"]\njobs = 2\nwasm-pack-args = [\"--headless\"]\n",
+    )
+    .unwrap();
+    let app_path = dir.path().join("app").display().to_string();
+
+    let mut cmd = init_cmd(&dir);
+    cmd.arg("--list");
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert!(
+        stdout.contains(&format!("+ wasm-pack test --node {app_path}\n")),
+        "{}",
+        stdout_stderr
+    );
+    assert!(!stdout.contains("demo"), "{}", stdout_stderr);
+
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["--list", "--profile", "ci"]);
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert!(
+        stdout.contains(&format!(
+            "+ wasm-pack test --chrome --headless {app_path}\n"
+        )),
+        "{}",
+        stdout_stderr
+    );
+
+    // The options of the command line override the profile
+    let mut cmd = init_cmd(&dir);
+    cmd.args([
+        "app",
+        "--print-config",
+        "--profile=ci",
+        "-j",
+        "3",
+        "--exclude",
+        "legacy",
+    ]);
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert!(
+        stdout.starts_with("# Configuration file: "),
+        "{}",
+        stdout_stderr
+    );
+    assert!(
+        !stdout.contains("[wasm-pack-test-all]"),
+        "{}",
+        stdout_stderr
+    );
+    for setting in [
+        "(profile ci)\n",
+        "\nruntimes = [\"chrome\"]\n",
+        "\njobs = 3\n",
+        "\nexclude = [\"examples/\"]\n",
+        "\nwasm-pack-args = [\"--headless\"]\n",
+    ] {
+        assert!(stdout.contains(setting), "{}", stdout_stderr);
+    }
+    // The patterns of the command line are relative to the path, not to the
+    // directory of the configuration file
+    assert!(
+        stdout.contains(&format!(
+            "# Also excluded, relative to {app_path}: [\"legacy\"]\n"
+        )),
+        "{}",
+        stdout_stderr
+    );
+
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["--list", "--profile", "local"]);
+    let output = cmd.output().unwrap();
+    assert_eq!(output.status.code(), Some(8));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("the profile `local` is not defined"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn auto_runtime_selects_the_runtime_configured_by_each_crate() {
+    let dir = tempdir();
+    let with_tests = "#[wasm_bindgen_test]\nfn foo() {}\n";
+    create_crates_with_librs(
+        &dir,
+        &[
+            (
+                "native",
+                &format!("// wasm_bindgen_test_configure!(run_in_browser);\n{with_tests}"),
+            ),
+            (
+                "browser",
+                &format!("wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);\n{with_tests}"),
+            ),
+            (
+                "worker",
+                &format!("use wasm_bindgen_test::wasm_bindgen_test_configure as configure;\nconfigure!(run_in_shared_worker);\n{with_tests}"),
+            ),
+        ],
+    );
+    let crate_path = |name: &str| dir.path().join(name).display().to_string();
+
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["--list", "--auto-runtime", "--firefox", "--headless"]);
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    for (name, runtime) in [
+        ("native", "--node"),
+        ("browser", "--firefox"),
+        ("worker", "--firefox"),
+    ] {
+        assert!(
+            stdout.contains(&format!(
+                "+ wasm-pack test {runtime} --headless {}\n",
+                crate_path(name)
+            )),
+            "{}",
+            stdout_stderr
+        );
+    }
+
+    // Headless Chrome is used when no browser is selected, passing
+    // `--headless` once if the crate configuration passes it too
+    let worker_manifest_path = dir.path().join("worker").join("Cargo.toml");
+    let worker_manifest = std::fs::read_to_string(&worker_manifest_path).unwrap();
+    std::fs::write(
+        &worker_manifest_path,
+        format!("{worker_manifest}\n[package.metadata.wasm-pack-test-all]\nwasm-pack-args = [\"--headless\"]\n"),
+    )
+    .unwrap();
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["--list", "--auto-runtime", "--node"]);
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    assert!(
+        stdout.contains(&format!(
+            "+ wasm-pack test --chrome --headless {}\n",
+            crate_path("browser")
+        )),
+        "{}",
+        stdout_stderr
+    );
+    assert!(
+        stdout.contains(&format!(
+            "+ wasm-pack test --chrome --headless {}\n",
+            crate_path("worker")
+        )),
+        "{}",
+        stdout_stderr
+    );
+    assert!(
+        stdout.contains(&format!(
+            "+ wasm-pack test --node {}\n",
+            crate_path("native")
+        )),
+        "{}",
+        stdout_stderr
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn runtimes_matrix_tests_each_crate_in_each_runtime() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    use_fake_wasm_pack(
+        &mut cmd,
+        &dir,
+        r#"
+for arg in "$@"; do
+    if [ -d "$arg" ]; then crate=$(basename "$arg"); fi
+    if [ "$arg" = "--firefox" ]; then runtime=firefox; fi
+done
+[ "$crate" != "bar" ] || [ "$runtime" != "firefox" ]
+"#,
+    );
+    cmd.args(["--runtimes", "node,firefox", "--junit", "report.xml"]);
+    create_foo_and_bar_crates(&dir);
+    let foo_manifest_path = dir.path().join("foo").join("Cargo.toml");
+    let foo_manifest = std::fs::read_to_string(&foo_manifest_path).unwrap();
+    std::fs::write(
+        &foo_manifest_path,
+        format!("{foo_manifest}\n[package.metadata.wasm-pack-test-all]\nruntimes = [\"node\"]\n"),
+    )
+    .unwrap();
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert_eq!(output.status.code(), Some(6), "{}", stdout_stderr);
+    assert!(
+        stdout.contains("  - foo (firefox) (skipped: runtime firefox not allowed)\n"),
+        "{}",
+        stdout_stderr
+    );
+    let bar_path = dir.path().join("bar").display().to_string();
+    assert!(
+        stdout.contains(&format!("+ wasm-pack test --firefox {bar_path}\n")),
+        "{}",
+        stdout_stderr
+    );
+    for line in [
+        "Runtimes matrix:\n",
+        "  CRATE  node    firefox\n",
+        "  foo    passed  -\n",
+        "  bar    passed  failed\n",
+        "in 3 runs",
+    ] {
+        assert!(stdout.contains(line), "{}", stdout_stderr);
+    }
+    assert!(
+        stderr.contains("Failed crates:\n[wasm-pack-test-all]   - bar (firefox)\n"),
+        "{}",
+        stdout_stderr
+    );
+
+    let report = std::fs::read_to_string(dir.path().join("report.xml")).unwrap();
+    assert!(
+        report.contains(r#"<testsuite name="bar (firefox)" tests="1" failures="1""#),
+        "{}",
+        report
+    );
+    assert!(
+        report.contains(r#"<property name="runtime" value="firefox"/>"#),
+        "{}",
+        report
+    );
+
+    let mut cmd = init_cmd(&dir);
+    cmd.args(["--list", "--runtimes", "node,edge"]);
+    let output = cmd.output().unwrap();
+    assert_eq!(output.status.code(), Some(8));
+}
+
+#[cfg(unix)]
+#[test]
+fn config_file_runtimes_are_a_matrix() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    use_fake_wasm_pack(&mut cmd, &dir, "true");
+    cmd.args(["--profile", "ci"]);
+    create_foo_and_bar_crates(&dir);
+    std::fs::write(
+        dir.path().join("wasm-pack-test-all.toml"),
+        "[profile.ci]\nruntimes = [\"chrome\", \"firefox\"]\nwasm-pack-args = [\"--headless\"]\n",
+    )
+    .unwrap();
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_stderr = format!("STDOUT: {}\n\nSTDERR: {}", stdout, stderr);
+    assert!(output.status.success(), "{}", stdout_stderr);
+    let foo_path = dir.path().join("foo").display().to_string();
+    for line in [
+        format!("+ wasm-pack test --chrome --headless {foo_path}\n"),
+        format!("+ wasm-pack test --firefox --headless {foo_path}\n"),
+        "Runtimes matrix:\n".to_string(),
+        "  CRATE  chrome  firefox\n".to_string(),
+        "in 4 runs".to_string(),
+    ] {
+        assert!(stdout.contains(&line), "{}", stdout_stderr);
+    }
+}